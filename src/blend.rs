@@ -0,0 +1,430 @@
+//! Per-image blend modes and opacity, with an offscreen compositing pass for modes the stage can't blend.
+use std::collections::HashMap;
+
+use craballoc::value::Hybrid;
+use renderling::{
+    atlas::{AtlasImage, AtlasTexture},
+    camera::Camera,
+    stage::Stage,
+    Context,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{now_seconds, ImageObject, ImageStage, State, WindowState};
+
+// Tekstury z przemnożonym alpha trzymamy w atlasie pod osobnym kluczem
+pub const PREMULTIPLIED_SUFFIX: &str = "#premultiplied";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum BlendMode {
+    #[default]
+    Alpha,
+    // kolory tekstury są już przemnożone przez alpha
+    Premultiplied,
+    Additive,
+    Multiply,
+    Screen,
+}
+
+impl BlendMode {
+    // Renderling miesza tylko zwykłym alpha – pozostałe tryby rysujemy do osobnej
+    // tekstury i nakładamy na klatkę własnym przebiegiem
+    pub fn is_composited(self) -> bool {
+        matches!(
+            self,
+            BlendMode::Additive | BlendMode::Multiply | BlendMode::Screen
+        )
+    }
+
+    // Tło tekstury pośredniej to kolor neutralny dla trybu, więc przezroczyste
+    // fragmenty sprite'a nie zmieniają klatki
+    fn clear_color(self) -> [f32; 4] {
+        match self {
+            BlendMode::Multiply => [1.0, 1.0, 1.0, 1.0],
+            _ => [0.0, 0.0, 0.0, 1.0],
+        }
+    }
+
+    fn blend_state(self) -> wgpu::BlendState {
+        let (src_factor, dst_factor) = match self {
+            BlendMode::Additive => (wgpu::BlendFactor::One, wgpu::BlendFactor::One),
+            BlendMode::Multiply => (wgpu::BlendFactor::Dst, wgpu::BlendFactor::Zero),
+            BlendMode::Screen => (wgpu::BlendFactor::OneMinusDst, wgpu::BlendFactor::One),
+            BlendMode::Alpha | BlendMode::Premultiplied => {
+                (wgpu::BlendFactor::One, wgpu::BlendFactor::OneMinusSrcAlpha)
+            }
+        };
+        wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor,
+                dst_factor,
+                operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent::REPLACE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OpacityFade {
+    pub from: f32,
+    pub to: f32,
+    pub start: f64,
+    pub duration: f32,
+}
+
+pub fn texture_key(path: &str, blend: BlendMode) -> String {
+    match blend {
+        BlendMode::Premultiplied => format!("{path}{PREMULTIPLIED_SUFFIX}"),
+        _ => path.to_string(),
+    }
+}
+
+// Ładuje obraz dla klucza z `texture_key` – wersja "premultiplied" jest cofana do zwykłego
+// alpha, bo tak miesza pipeline renderlinga
+pub fn load_atlas_image(key: &str) -> anyhow::Result<AtlasImage> {
+    let (path, premultiplied) = match key.strip_suffix(PREMULTIPLIED_SUFFIX) {
+        Some(path) => (path, true),
+        None => (key, false),
+    };
+    let mut atlas_image = AtlasImage::from_path(path)
+        .map_err(|e| anyhow::anyhow!("Failed to load texture '{}': {:?}", path, e))?;
+    if premultiplied {
+        unpremultiply(&mut atlas_image);
+    }
+    Ok(atlas_image)
+}
+
+fn unpremultiply(img: &mut AtlasImage) {
+    let pixel_count = (img.size.x * img.size.y) as usize;
+    if pixel_count == 0 || img.pixels.len() != pixel_count * 4 {
+        log::warn!("Premultiplied alpha is supported only for 8-bit RGBA textures");
+        return;
+    }
+    for px in img.pixels.chunks_exact_mut(4) {
+        let a = px[3] as u32;
+        if a == 0 || a == 255 {
+            continue;
+        }
+        for c in &mut px[..3] {
+            *c = ((*c as u32 * 255 + a / 2) / a).min(255) as u8;
+        }
+    }
+}
+
+// Fullscreen triangle, który kopiuje teksturę pośrednią piksel w piksel
+const COMPOSITE_SHADER: &str = r#"
+@group(0) @binding(0) var layer: texture_2d<f32>;
+
+@vertex
+fn vs_main(@builtin(vertex_index) i: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((i << 1u) & 2u), f32(i & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
+    return textureLoad(layer, vec2<i32>(pos.xy), 0);
+}
+"#;
+
+pub struct BlendCompositor {
+    layout: wgpu::BindGroupLayout,
+    pipelines: HashMap<BlendMode, wgpu::RenderPipeline>,
+}
+
+impl BlendCompositor {
+    fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("blend composite"),
+            source: wgpu::ShaderSource::Wgsl(COMPOSITE_SHADER.into()),
+        });
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("blend composite"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("blend composite"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipelines = [BlendMode::Additive, BlendMode::Multiply, BlendMode::Screen]
+            .into_iter()
+            .map(|mode| {
+                let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some("blend composite"),
+                    layout: Some(&pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader,
+                        entry_point: Some("vs_main"),
+                        compilation_options: Default::default(),
+                        buffers: &[],
+                    },
+                    primitive: wgpu::PrimitiveState::default(),
+                    depth_stencil: None,
+                    multisample: wgpu::MultisampleState::default(),
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader,
+                        entry_point: Some("fs_main"),
+                        compilation_options: Default::default(),
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format,
+                            blend: Some(mode.blend_state()),
+                            write_mask: wgpu::ColorWrites::COLOR,
+                        })],
+                    }),
+                    multiview: None,
+                    cache: None,
+                });
+                (mode, pipeline)
+            })
+            .collect();
+
+        Self { layout, pipelines }
+    }
+
+    fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        mode: BlendMode,
+        target: &BlendTarget,
+        frame_view: &wgpu::TextureView,
+    ) {
+        let Some(pipeline) = self.pipelines.get(&mode) else {
+            return;
+        };
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("blend composite"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: frame_view,
+                depth_slice: None,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, &target.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
+
+struct BlendTarget {
+    size: glam::UVec2,
+    view: wgpu::TextureView,
+    bind_group: wgpu::BindGroup,
+}
+
+impl BlendTarget {
+    fn new(
+        device: &wgpu::Device,
+        compositor: &BlendCompositor,
+        format: wgpu::TextureFormat,
+        size: glam::UVec2,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("blend layer"),
+            size: wgpu::Extent3d {
+                width: size.x.max(1),
+                height: size.y.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("blend layer"),
+            layout: &compositor.layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&view),
+            }],
+        });
+        Self {
+            size,
+            view,
+            bind_group,
+        }
+    }
+}
+
+// Osobny stage dla jednego trybu mieszania – sprite'y nie są testowane głębią
+// względem głównej sceny, bo trafiają na klatkę dopiero po niej
+pub struct BlendLayer {
+    pub stage: Stage,
+    pub camera: Hybrid<Camera>,
+    pub tex: HashMap<String, Hybrid<AtlasTexture>>,
+    target: Option<BlendTarget>,
+}
+
+impl BlendLayer {
+    pub fn new(ctx: &Context, mode: BlendMode, camera: Camera) -> Self {
+        let stage = ctx
+            .new_stage()
+            .with_background_color(mode.clear_color())
+            .with_lighting(false)
+            .with_size(ctx.get_size());
+        let camera = stage.new_value(camera);
+        Self {
+            stage,
+            camera,
+            tex: HashMap::new(),
+            target: None,
+        }
+    }
+}
+
+impl ImageObject {
+    // Alpha obrazka to alpha z tintu razy opacity – tekstura zostaje bez zmian
+    pub fn apply_color(&self) {
+        let color = glam::Vec4::from(self.base_color) * glam::Vec4::from(self.tint);
+        let mut mat = self.material.get();
+        mat.albedo_factor = color * glam::Vec4::new(1.0, 1.0, 1.0, self.opacity);
+        self.material.set(mat);
+    }
+
+    fn advance_fade(&mut self, now: f64) {
+        let Some(fade) = self.fade else {
+            return;
+        };
+        let t = if fade.duration <= 0.0 {
+            1.0
+        } else {
+            ((now - fade.start) as f32 / fade.duration).clamp(0.0, 1.0)
+        };
+        self.opacity = fade.from + (fade.to - fade.from) * t;
+        self.apply_color();
+        if t >= 1.0 {
+            self.fade = None;
+        }
+    }
+}
+
+impl WindowState {
+    // Wywoływane przed każdą klatką
+    pub fn advance_fades(&mut self, now: f64) {
+        for image in self.images.values_mut() {
+            image.advance_fade(now);
+        }
+    }
+
+    // Rysuje stage trybów mieszania do tekstur pośrednich i nakłada je na klatkę
+    pub fn render_blend_layers(&mut self, frame_view: &wgpu::TextureView) {
+        if self.blend_layers.is_empty() {
+            return;
+        }
+        // Multiply i Additive nie są przemienne, więc kolejność nakładania musi być stała:
+        // wg najniższej warstwy/z-order obrazków danego trybu, a przy remisie wg trybu
+        let mut modes: Vec<((i32, i32), BlendMode)> = self
+            .blend_layers
+            .keys()
+            .filter_map(|mode| {
+                self.images
+                    .values()
+                    .filter(|image| image.stage == ImageStage::Blend(*mode))
+                    .map(|image| (self.layer_order(&image.layer), image.z_order))
+                    .min()
+                    .map(|key| (key, *mode))
+            })
+            .collect();
+        modes.sort_by_key(|(key, mode)| (*key, *mode as u8));
+
+        let device = self.ctx.get_device();
+        let format = self.ctx.get_render_target().format();
+        let size = self.ctx.get_size();
+        let compositor = self
+            .compositor
+            .get_or_insert_with(|| BlendCompositor::new(device, format));
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("blend composite"),
+        });
+        for (_, mode) in modes {
+            let layer = self.blend_layers.get_mut(&mode).unwrap();
+            if layer.target.as_ref().map(|target| target.size) != Some(size) {
+                layer.target = Some(BlendTarget::new(device, compositor, format, size));
+            }
+            let target = layer.target.as_ref().unwrap();
+            layer.stage.render(&target.view);
+            compositor.draw(&mut encoder, mode, target, frame_view);
+        }
+        self.ctx.get_queue().submit(Some(encoder.finish()));
+    }
+}
+
+impl State {
+    pub fn set_image_blend_mode(
+        &mut self,
+        window_id: u32,
+        name: &str,
+        mode: BlendMode,
+    ) -> anyhow::Result<()> {
+        let ws = self
+            .windows
+            .get_mut(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+        let image = ws
+            .images
+            .get_mut(name)
+            .ok_or_else(|| anyhow::anyhow!("No image with name '{}'", name))?;
+        let previous = image.blend;
+        image.blend = mode;
+
+        if let Err(e) = ws.rebind_image(name) {
+            ws.images.get_mut(name).unwrap().blend = previous;
+            return Err(e);
+        }
+        // nowy materiał dostaje tint i opacity obrazka
+        ws.images[name].apply_color();
+        ws.sort_images();
+        Ok(())
+    }
+
+    // Zmienia przezroczystość płynnie w ciągu `duration` sekund (0 = od razu).
+    // Obrazek z materiałem nazwanym ma jego kopię, więc zmiana dotyczy tylko tego obrazka.
+    pub fn set_image_opacity(
+        &mut self,
+        window_id: u32,
+        name: &str,
+        opacity: f32,
+        duration: f32,
+    ) -> anyhow::Result<()> {
+        let ws = self
+            .windows
+            .get_mut(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+        let image = ws
+            .images
+            .get_mut(name)
+            .ok_or_else(|| anyhow::anyhow!("No image with name '{}'", name))?;
+        let now = now_seconds();
+        image.fade = Some(OpacityFade {
+            from: image.opacity,
+            to: opacity.clamp(0.0, 1.0),
+            start: now,
+            duration,
+        });
+        image.advance_fade(now);
+        Ok(())
+    }
+}
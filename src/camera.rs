@@ -0,0 +1,30 @@
+//! Window camera bound to every stage of the window.
+use renderling::camera::Camera;
+
+use crate::{ImageStage, WindowState};
+
+impl WindowState {
+    // Uchwyt kamery w slabie danego stage – renderlety muszą wskazywać kamerę z tego samego stage
+    pub fn stage_camera(&self, stage: ImageStage) -> &craballoc::value::Hybrid<Camera> {
+        match stage {
+            ImageStage::Main => &self.main_camera,
+            ImageStage::Overlay(key) => &self.overlays[&key].camera,
+            ImageStage::Blend(mode) => &self.blend_layers[&mode].camera,
+        }
+    }
+
+    // Ustawia kamerę okna na wszystkich jego stage'ach
+    pub fn set_camera(&mut self, camera: Camera) {
+        self.camera = camera;
+        self.main_camera.set(camera);
+        for pass in self.overlays.values() {
+            pass.camera.set(camera);
+        }
+        for layer in self.blend_layers.values() {
+            layer.camera.set(camera);
+        }
+        // odległość od kamery decyduje o kolejności półprzezroczystych obrazków
+        self.sort_images();
+        self.needs_redraw = true;
+    }
+}
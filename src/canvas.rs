@@ -0,0 +1,29 @@
+//! Canvas elements backing windows in the browser build.
+use wasm_bindgen::JsCast;
+
+// Canvas okna 0 można podać w adresie strony: `index.html?canvas=moj-canvas`
+pub fn canvas_from_url() -> Option<String> {
+    let search = web_sys::window()?.location().search().ok()?;
+    web_sys::UrlSearchParams::new_with_str(&search)
+        .ok()?
+        .get("canvas")
+}
+
+pub fn find_canvas(id: &str) -> anyhow::Result<web_sys::HtmlCanvasElement> {
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or_else(|| anyhow::anyhow!("No browser document"))?;
+    document
+        .get_element_by_id(id)
+        .ok_or_else(|| anyhow::anyhow!("No element with id '{}'", id))?
+        .dyn_into::<web_sys::HtmlCanvasElement>()
+        .map_err(|_| anyhow::anyhow!("Element '{}' is not a canvas", id))
+}
+
+// Komendy są serializowalne, więc element bez id dostaje id wygenerowane z numeru okna
+pub fn canvas_id(canvas: &web_sys::HtmlCanvasElement, window: u32) -> String {
+    if canvas.id().is_empty() {
+        canvas.set_id(&format!("renderling-canvas-{}", window));
+    }
+    canvas.id()
+}
@@ -0,0 +1,1186 @@
+//! Serializable commands mirroring `CustomUserEvent`, used by timeline scripts and recordings.
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    BlendMode, ClosePolicy, CustomUserEvent, FullscreenMode, LightDescriptor, MaterialDescriptor,
+    RedrawPolicy, ShadowSettings,
+};
+
+fn default_true() -> bool {
+    true
+}
+
+// Każdy wariant odpowiada jednemu wariantowi `CustomUserEvent`, ale z nazwanymi polami,
+// żeby pliki RON/JSON dało się czytać i pisać ręcznie
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Command {
+    CreateWindow {
+        id: u32,
+        width: u32,
+        height: u32,
+        title: String,
+        background_color: [f32; 4],
+        #[serde(default = "default_true")]
+        visible: bool,
+    },
+    DeleteWindow {
+        id: u32,
+    },
+    // tylko wasm: okno rysujące na istniejącym elemencie <canvas> o danym id
+    AttachCanvas {
+        id: u32,
+        canvas: String,
+        background_color: [f32; 4],
+    },
+    LoadTexture {
+        window: u32,
+        path: String,
+    },
+    AddImage {
+        window: u32,
+        name: String,
+        texture: String,
+        x: f32,
+        y: f32,
+        z: f32,
+    },
+    SetImagePosition {
+        window: u32,
+        name: String,
+        x: f32,
+        y: f32,
+        z: f32,
+    },
+    SetImageSize {
+        window: u32,
+        name: String,
+        width: u32,
+        height: u32,
+    },
+    SetImageRotation {
+        window: u32,
+        name: String,
+        angle: f32,
+    },
+    DeleteImage {
+        window: u32,
+        name: String,
+    },
+    DeleteTexture {
+        window: u32,
+        path: String,
+    },
+    LoadModel {
+        window: u32,
+        name: String,
+        path: String,
+        x: f32,
+        y: f32,
+        z: f32,
+    },
+    SetModelPosition {
+        window: u32,
+        name: String,
+        x: f32,
+        y: f32,
+        z: f32,
+    },
+    SetModelRotation {
+        window: u32,
+        name: String,
+        x: f32,
+        y: f32,
+        z: f32,
+    },
+    SetModelScale {
+        window: u32,
+        name: String,
+        x: f32,
+        y: f32,
+        z: f32,
+    },
+    DeleteModel {
+        window: u32,
+        name: String,
+    },
+    ListAnimations {
+        window: u32,
+        model: String,
+    },
+    PlayAnimation {
+        window: u32,
+        model: String,
+        animation: String,
+        speed: f32,
+        looping: bool,
+    },
+    PauseAnimation {
+        window: u32,
+        model: String,
+        animation: String,
+    },
+    StopAnimation {
+        window: u32,
+        model: String,
+        animation: String,
+    },
+    SeekAnimation {
+        window: u32,
+        model: String,
+        animation: String,
+        time: f32,
+    },
+    SetAnimationWeight {
+        window: u32,
+        model: String,
+        animation: String,
+        weight: f32,
+    },
+    SetLighting {
+        window: u32,
+        enabled: bool,
+    },
+    AddLight {
+        window: u32,
+        name: String,
+        light: LightDescriptor,
+    },
+    SetLight {
+        window: u32,
+        name: String,
+        light: LightDescriptor,
+    },
+    DeleteLight {
+        window: u32,
+        name: String,
+    },
+    EnableShadows {
+        window: u32,
+        light: String,
+        #[serde(default)]
+        settings: ShadowSettings,
+    },
+    DisableShadows {
+        window: u32,
+        light: String,
+    },
+    // cienie odbiera każdy oświetlony obiekt głównego stage – renderling nie ma osobnej flagi;
+    // obiekt bez cieni to materiał `unlit` albo warstwa `ignore_depth`
+    SetCastShadows {
+        window: u32,
+        name: String,
+        cast: bool,
+    },
+    SetEnvironment {
+        window: u32,
+        hdr_path: String,
+    },
+    SetEnvironmentExposure {
+        window: u32,
+        exposure: f32,
+    },
+    SetEnvironmentRotation {
+        window: u32,
+        angle: f32,
+    },
+    CreateMaterial {
+        window: u32,
+        name: String,
+        #[serde(default)]
+        material: MaterialDescriptor,
+    },
+    SetMaterial {
+        window: u32,
+        name: String,
+        material: MaterialDescriptor,
+    },
+    DeleteMaterial {
+        window: u32,
+        name: String,
+    },
+    AssignMaterial {
+        window: u32,
+        name: String,
+        material: String,
+    },
+    SetImageTint {
+        window: u32,
+        name: String,
+        color: [f32; 4],
+    },
+    SetLayer {
+        window: u32,
+        name: String,
+        order: i32,
+        ignore_depth: bool,
+    },
+    DeleteLayer {
+        window: u32,
+        name: String,
+    },
+    SetImageLayer {
+        window: u32,
+        name: String,
+        layer: String,
+    },
+    SetImageZOrder {
+        window: u32,
+        name: String,
+        z_order: i32,
+    },
+    SetImageBlendMode {
+        window: u32,
+        name: String,
+        mode: BlendMode,
+    },
+    SetImageOpacity {
+        window: u32,
+        name: String,
+        opacity: f32,
+        #[serde(default)]
+        duration: f32,
+    },
+    SetImageVisible {
+        window: u32,
+        name: String,
+        visible: bool,
+    },
+    SetLayerVisible {
+        window: u32,
+        name: String,
+        visible: bool,
+    },
+    SetImageHitAlpha {
+        window: u32,
+        name: String,
+        threshold: f32,
+    },
+    SetParent {
+        window: u32,
+        child: String,
+        parent: String,
+    },
+    DetachFromParent {
+        window: u32,
+        child: String,
+    },
+    DeleteObject {
+        window: u32,
+        name: String,
+        #[serde(default)]
+        recursive: bool,
+    },
+    SaveScene {
+        window: u32,
+        path: String,
+    },
+    LoadScene {
+        window: u32,
+        path: String,
+    },
+    SetClosePolicy {
+        window: u32,
+        policy: ClosePolicy,
+    },
+    SetWindowPosition {
+        window: u32,
+        x: i32,
+        y: i32,
+    },
+    SetWindowSize {
+        window: u32,
+        width: u32,
+        height: u32,
+    },
+    SetWindowTitle {
+        window: u32,
+        title: String,
+    },
+    SetFullscreen {
+        window: u32,
+        mode: FullscreenMode,
+    },
+    SetDecorations {
+        window: u32,
+        decorations: bool,
+    },
+    SetAlwaysOnTop {
+        window: u32,
+        always_on_top: bool,
+    },
+    SetVisible {
+        window: u32,
+        visible: bool,
+    },
+    SetCursorVisible {
+        window: u32,
+        visible: bool,
+    },
+    SetCursorIcon {
+        window: u32,
+        icon: String,
+    },
+    Minimize {
+        window: u32,
+        minimized: bool,
+    },
+    Maximize {
+        window: u32,
+        maximized: bool,
+    },
+    SetRedrawPolicy {
+        window: u32,
+        policy: RedrawPolicy,
+    },
+}
+
+impl Command {
+    // Okno, na którym działa komenda
+    pub fn window(&self) -> u32 {
+        match self {
+            Command::CreateWindow { id, .. }
+            | Command::DeleteWindow { id }
+            | Command::AttachCanvas { id, .. } => *id,
+            Command::LoadTexture { window, .. }
+            | Command::AddImage { window, .. }
+            | Command::SetImagePosition { window, .. }
+            | Command::SetImageSize { window, .. }
+            | Command::SetImageRotation { window, .. }
+            | Command::DeleteImage { window, .. }
+            | Command::DeleteTexture { window, .. }
+            | Command::LoadModel { window, .. }
+            | Command::SetModelPosition { window, .. }
+            | Command::SetModelRotation { window, .. }
+            | Command::SetModelScale { window, .. }
+            | Command::DeleteModel { window, .. }
+            | Command::ListAnimations { window, .. }
+            | Command::PlayAnimation { window, .. }
+            | Command::PauseAnimation { window, .. }
+            | Command::StopAnimation { window, .. }
+            | Command::SeekAnimation { window, .. }
+            | Command::SetAnimationWeight { window, .. }
+            | Command::SetLighting { window, .. }
+            | Command::AddLight { window, .. }
+            | Command::SetLight { window, .. }
+            | Command::DeleteLight { window, .. }
+            | Command::EnableShadows { window, .. }
+            | Command::DisableShadows { window, .. }
+            | Command::SetCastShadows { window, .. }
+            | Command::SetEnvironment { window, .. }
+            | Command::SetEnvironmentExposure { window, .. }
+            | Command::SetEnvironmentRotation { window, .. }
+            | Command::CreateMaterial { window, .. }
+            | Command::SetMaterial { window, .. }
+            | Command::DeleteMaterial { window, .. }
+            | Command::AssignMaterial { window, .. }
+            | Command::SetImageTint { window, .. }
+            | Command::SetLayer { window, .. }
+            | Command::DeleteLayer { window, .. }
+            | Command::SetImageLayer { window, .. }
+            | Command::SetImageZOrder { window, .. }
+            | Command::SetImageBlendMode { window, .. }
+            | Command::SetImageOpacity { window, .. }
+            | Command::SetImageVisible { window, .. }
+            | Command::SetImageHitAlpha { window, .. }
+            | Command::SetLayerVisible { window, .. }
+            | Command::SetParent { window, .. }
+            | Command::DetachFromParent { window, .. }
+            | Command::DeleteObject { window, .. }
+            | Command::SaveScene { window, .. }
+            | Command::LoadScene { window, .. }
+            | Command::SetClosePolicy { window, .. }
+            | Command::SetWindowPosition { window, .. }
+            | Command::SetWindowSize { window, .. }
+            | Command::SetWindowTitle { window, .. }
+            | Command::SetFullscreen { window, .. }
+            | Command::SetDecorations { window, .. }
+            | Command::SetAlwaysOnTop { window, .. }
+            | Command::SetVisible { window, .. }
+            | Command::SetCursorVisible { window, .. }
+            | Command::SetCursorIcon { window, .. }
+            | Command::Minimize { window, .. }
+            | Command::Maximize { window, .. }
+            | Command::SetRedrawPolicy { window, .. } => *window,
+        }
+    }
+
+    pub fn into_event(self) -> CustomUserEvent {
+        match self {
+            Command::CreateWindow {
+                id,
+                width,
+                height,
+                title,
+                background_color,
+                visible,
+            } => CustomUserEvent::CreateWindow(
+                id,
+                width,
+                height,
+                title,
+                palette::Srgba::new(
+                    background_color[0],
+                    background_color[1],
+                    background_color[2],
+                    background_color[3],
+                ),
+                visible,
+            ),
+            Command::DeleteWindow { id } => CustomUserEvent::DeleteWindow(id),
+            Command::AttachCanvas {
+                id,
+                canvas,
+                background_color,
+            } => CustomUserEvent::AttachCanvas(
+                id,
+                canvas,
+                palette::Srgba::new(
+                    background_color[0],
+                    background_color[1],
+                    background_color[2],
+                    background_color[3],
+                ),
+            ),
+            Command::LoadTexture { window, path } => CustomUserEvent::LoadTexture(window, path),
+            Command::AddImage {
+                window,
+                name,
+                texture,
+                x,
+                y,
+                z,
+            } => CustomUserEvent::AddImage(window, name, texture, x, y, z),
+            Command::SetImagePosition {
+                window,
+                name,
+                x,
+                y,
+                z,
+            } => CustomUserEvent::SetImagePosition(window, name, x, y, z),
+            Command::SetImageSize {
+                window,
+                name,
+                width,
+                height,
+            } => CustomUserEvent::SetImageSize(window, name, width, height),
+            Command::SetImageRotation {
+                window,
+                name,
+                angle,
+            } => CustomUserEvent::SetImageRotation(window, name, angle),
+            Command::DeleteImage { window, name } => CustomUserEvent::DeleteImage(window, name),
+            Command::DeleteTexture { window, path } => CustomUserEvent::DeleteTexture(window, path),
+            Command::LoadModel {
+                window,
+                name,
+                path,
+                x,
+                y,
+                z,
+            } => CustomUserEvent::LoadModel(window, name, path, x, y, z),
+            Command::SetModelPosition {
+                window,
+                name,
+                x,
+                y,
+                z,
+            } => CustomUserEvent::SetModelPosition(window, name, x, y, z),
+            Command::SetModelRotation {
+                window,
+                name,
+                x,
+                y,
+                z,
+            } => CustomUserEvent::SetModelRotation(window, name, x, y, z),
+            Command::SetModelScale {
+                window,
+                name,
+                x,
+                y,
+                z,
+            } => CustomUserEvent::SetModelScale(window, name, x, y, z),
+            Command::DeleteModel { window, name } => CustomUserEvent::DeleteModel(window, name),
+            Command::ListAnimations { window, model } => {
+                CustomUserEvent::ListAnimations(window, model)
+            }
+            Command::PlayAnimation {
+                window,
+                model,
+                animation,
+                speed,
+                looping,
+            } => CustomUserEvent::PlayAnimation(window, model, animation, speed, looping),
+            Command::PauseAnimation {
+                window,
+                model,
+                animation,
+            } => CustomUserEvent::PauseAnimation(window, model, animation),
+            Command::StopAnimation {
+                window,
+                model,
+                animation,
+            } => CustomUserEvent::StopAnimation(window, model, animation),
+            Command::SeekAnimation {
+                window,
+                model,
+                animation,
+                time,
+            } => CustomUserEvent::SeekAnimation(window, model, animation, time),
+            Command::SetAnimationWeight {
+                window,
+                model,
+                animation,
+                weight,
+            } => CustomUserEvent::SetAnimationWeight(window, model, animation, weight),
+            Command::SetLighting { window, enabled } => {
+                CustomUserEvent::SetLighting(window, enabled)
+            }
+            Command::AddLight {
+                window,
+                name,
+                light,
+            } => CustomUserEvent::AddLight(window, name, light),
+            Command::SetLight {
+                window,
+                name,
+                light,
+            } => CustomUserEvent::SetLight(window, name, light),
+            Command::DeleteLight { window, name } => CustomUserEvent::DeleteLight(window, name),
+            Command::EnableShadows {
+                window,
+                light,
+                settings,
+            } => CustomUserEvent::EnableShadows(window, light, settings),
+            Command::DisableShadows { window, light } => {
+                CustomUserEvent::DisableShadows(window, light)
+            }
+            Command::SetCastShadows { window, name, cast } => {
+                CustomUserEvent::SetCastShadows(window, name, cast)
+            }
+            Command::SetEnvironment { window, hdr_path } => {
+                CustomUserEvent::SetEnvironment(window, hdr_path)
+            }
+            Command::SetEnvironmentExposure { window, exposure } => {
+                CustomUserEvent::SetEnvironmentExposure(window, exposure)
+            }
+            Command::SetEnvironmentRotation { window, angle } => {
+                CustomUserEvent::SetEnvironmentRotation(window, angle)
+            }
+            Command::CreateMaterial {
+                window,
+                name,
+                material,
+            } => CustomUserEvent::CreateMaterial(window, name, material),
+            Command::SetMaterial {
+                window,
+                name,
+                material,
+            } => CustomUserEvent::SetMaterial(window, name, material),
+            Command::DeleteMaterial { window, name } => {
+                CustomUserEvent::DeleteMaterial(window, name)
+            }
+            Command::AssignMaterial {
+                window,
+                name,
+                material,
+            } => CustomUserEvent::AssignMaterial(window, name, material),
+            Command::SetImageTint {
+                window,
+                name,
+                color,
+            } => CustomUserEvent::SetImageTint(window, name, color),
+            Command::SetLayer {
+                window,
+                name,
+                order,
+                ignore_depth,
+            } => CustomUserEvent::SetLayer(window, name, order, ignore_depth),
+            Command::DeleteLayer { window, name } => CustomUserEvent::DeleteLayer(window, name),
+            Command::SetImageLayer {
+                window,
+                name,
+                layer,
+            } => CustomUserEvent::SetImageLayer(window, name, layer),
+            Command::SetImageZOrder {
+                window,
+                name,
+                z_order,
+            } => CustomUserEvent::SetImageZOrder(window, name, z_order),
+            Command::SetImageBlendMode { window, name, mode } => {
+                CustomUserEvent::SetImageBlendMode(window, name, mode)
+            }
+            Command::SetImageOpacity {
+                window,
+                name,
+                opacity,
+                duration,
+            } => CustomUserEvent::SetImageOpacity(window, name, opacity, duration),
+            Command::SetImageVisible {
+                window,
+                name,
+                visible,
+            } => CustomUserEvent::SetImageVisible(window, name, visible),
+            Command::SetImageHitAlpha {
+                window,
+                name,
+                threshold,
+            } => CustomUserEvent::SetImageHitAlpha(window, name, threshold),
+            Command::SetLayerVisible {
+                window,
+                name,
+                visible,
+            } => CustomUserEvent::SetLayerVisible(window, name, visible),
+            Command::SetParent {
+                window,
+                child,
+                parent,
+            } => CustomUserEvent::SetParent(window, child, parent),
+            Command::DetachFromParent { window, child } => {
+                CustomUserEvent::DetachFromParent(window, child)
+            }
+            Command::DeleteObject {
+                window,
+                name,
+                recursive,
+            } => CustomUserEvent::DeleteObject(window, name, recursive),
+            Command::SaveScene { window, path } => CustomUserEvent::SaveScene(window, path),
+            Command::LoadScene { window, path } => CustomUserEvent::LoadScene(window, path),
+            Command::SetClosePolicy { window, policy } => {
+                CustomUserEvent::SetClosePolicy(window, policy)
+            }
+            Command::SetWindowPosition { window, x, y } => {
+                CustomUserEvent::SetWindowPosition(window, x, y)
+            }
+            Command::SetWindowSize {
+                window,
+                width,
+                height,
+            } => CustomUserEvent::SetWindowSize(window, width, height),
+            Command::SetWindowTitle { window, title } => {
+                CustomUserEvent::SetWindowTitle(window, title)
+            }
+            Command::SetFullscreen { window, mode } => CustomUserEvent::SetFullscreen(window, mode),
+            Command::SetDecorations {
+                window,
+                decorations,
+            } => CustomUserEvent::SetDecorations(window, decorations),
+            Command::SetAlwaysOnTop {
+                window,
+                always_on_top,
+            } => CustomUserEvent::SetAlwaysOnTop(window, always_on_top),
+            Command::SetVisible { window, visible } => CustomUserEvent::SetVisible(window, visible),
+            Command::SetCursorVisible { window, visible } => {
+                CustomUserEvent::SetCursorVisible(window, visible)
+            }
+            Command::SetCursorIcon { window, icon } => CustomUserEvent::SetCursorIcon(window, icon),
+            Command::Minimize { window, minimized } => CustomUserEvent::Minimize(window, minimized),
+            Command::Maximize { window, maximized } => CustomUserEvent::Maximize(window, maximized),
+            Command::SetRedrawPolicy { window, policy } => {
+                CustomUserEvent::SetRedrawPolicy(window, policy)
+            }
+        }
+    }
+
+    // StateInitialized nie ma odpowiednika – to wewnętrzne zdarzenie startu aplikacji
+    pub fn from_event(event: &CustomUserEvent) -> Option<Self> {
+        let command = match event {
+            CustomUserEvent::StateInitialized(_) | CustomUserEvent::WindowReady(..) => return None,
+            CustomUserEvent::Command(command, _) => command.clone(),
+            CustomUserEvent::CreateWindow(id, width, height, title, background_color, visible) => {
+                Command::CreateWindow {
+                    id: *id,
+                    width: *width,
+                    height: *height,
+                    title: title.clone(),
+                    background_color: [
+                        background_color.red,
+                        background_color.green,
+                        background_color.blue,
+                        background_color.alpha,
+                    ],
+                    visible: *visible,
+                }
+            }
+            CustomUserEvent::DeleteWindow(id) => Command::DeleteWindow { id: *id },
+            CustomUserEvent::AttachCanvas(id, canvas, background_color) => Command::AttachCanvas {
+                id: *id,
+                canvas: canvas.clone(),
+                background_color: [
+                    background_color.red,
+                    background_color.green,
+                    background_color.blue,
+                    background_color.alpha,
+                ],
+            },
+            CustomUserEvent::LoadTexture(window, path) => Command::LoadTexture {
+                window: *window,
+                path: path.clone(),
+            },
+            CustomUserEvent::AddImage(window, name, texture, x, y, z) => Command::AddImage {
+                window: *window,
+                name: name.clone(),
+                texture: texture.clone(),
+                x: *x,
+                y: *y,
+                z: *z,
+            },
+            CustomUserEvent::SetImagePosition(window, name, x, y, z) => Command::SetImagePosition {
+                window: *window,
+                name: name.clone(),
+                x: *x,
+                y: *y,
+                z: *z,
+            },
+            CustomUserEvent::SetImageSize(window, name, width, height) => Command::SetImageSize {
+                window: *window,
+                name: name.clone(),
+                width: *width,
+                height: *height,
+            },
+            CustomUserEvent::SetImageRotation(window, name, angle) => Command::SetImageRotation {
+                window: *window,
+                name: name.clone(),
+                angle: *angle,
+            },
+            CustomUserEvent::DeleteImage(window, name) => Command::DeleteImage {
+                window: *window,
+                name: name.clone(),
+            },
+            CustomUserEvent::DeleteTexture(window, path) => Command::DeleteTexture {
+                window: *window,
+                path: path.clone(),
+            },
+            CustomUserEvent::LoadModel(window, name, path, x, y, z) => Command::LoadModel {
+                window: *window,
+                name: name.clone(),
+                path: path.clone(),
+                x: *x,
+                y: *y,
+                z: *z,
+            },
+            CustomUserEvent::SetModelPosition(window, name, x, y, z) => Command::SetModelPosition {
+                window: *window,
+                name: name.clone(),
+                x: *x,
+                y: *y,
+                z: *z,
+            },
+            CustomUserEvent::SetModelRotation(window, name, x, y, z) => Command::SetModelRotation {
+                window: *window,
+                name: name.clone(),
+                x: *x,
+                y: *y,
+                z: *z,
+            },
+            CustomUserEvent::SetModelScale(window, name, x, y, z) => Command::SetModelScale {
+                window: *window,
+                name: name.clone(),
+                x: *x,
+                y: *y,
+                z: *z,
+            },
+            CustomUserEvent::DeleteModel(window, name) => Command::DeleteModel {
+                window: *window,
+                name: name.clone(),
+            },
+            CustomUserEvent::ListAnimations(window, model) => Command::ListAnimations {
+                window: *window,
+                model: model.clone(),
+            },
+            CustomUserEvent::PlayAnimation(window, model, animation, speed, looping) => {
+                Command::PlayAnimation {
+                    window: *window,
+                    model: model.clone(),
+                    animation: animation.clone(),
+                    speed: *speed,
+                    looping: *looping,
+                }
+            }
+            CustomUserEvent::PauseAnimation(window, model, animation) => Command::PauseAnimation {
+                window: *window,
+                model: model.clone(),
+                animation: animation.clone(),
+            },
+            CustomUserEvent::StopAnimation(window, model, animation) => Command::StopAnimation {
+                window: *window,
+                model: model.clone(),
+                animation: animation.clone(),
+            },
+            CustomUserEvent::SeekAnimation(window, model, animation, time) => {
+                Command::SeekAnimation {
+                    window: *window,
+                    model: model.clone(),
+                    animation: animation.clone(),
+                    time: *time,
+                }
+            }
+            CustomUserEvent::SetAnimationWeight(window, model, animation, weight) => {
+                Command::SetAnimationWeight {
+                    window: *window,
+                    model: model.clone(),
+                    animation: animation.clone(),
+                    weight: *weight,
+                }
+            }
+            CustomUserEvent::SetLighting(window, enabled) => Command::SetLighting {
+                window: *window,
+                enabled: *enabled,
+            },
+            CustomUserEvent::AddLight(window, name, light) => Command::AddLight {
+                window: *window,
+                name: name.clone(),
+                light: *light,
+            },
+            CustomUserEvent::SetLight(window, name, light) => Command::SetLight {
+                window: *window,
+                name: name.clone(),
+                light: *light,
+            },
+            CustomUserEvent::DeleteLight(window, name) => Command::DeleteLight {
+                window: *window,
+                name: name.clone(),
+            },
+            CustomUserEvent::EnableShadows(window, light, settings) => Command::EnableShadows {
+                window: *window,
+                light: light.clone(),
+                settings: *settings,
+            },
+            CustomUserEvent::DisableShadows(window, light) => Command::DisableShadows {
+                window: *window,
+                light: light.clone(),
+            },
+            CustomUserEvent::SetCastShadows(window, name, cast) => Command::SetCastShadows {
+                window: *window,
+                name: name.clone(),
+                cast: *cast,
+            },
+            CustomUserEvent::SetEnvironment(window, hdr_path) => Command::SetEnvironment {
+                window: *window,
+                hdr_path: hdr_path.clone(),
+            },
+            CustomUserEvent::SetEnvironmentExposure(window, exposure) => {
+                Command::SetEnvironmentExposure {
+                    window: *window,
+                    exposure: *exposure,
+                }
+            }
+            CustomUserEvent::SetEnvironmentRotation(window, angle) => {
+                Command::SetEnvironmentRotation {
+                    window: *window,
+                    angle: *angle,
+                }
+            }
+            CustomUserEvent::CreateMaterial(window, name, material) => Command::CreateMaterial {
+                window: *window,
+                name: name.clone(),
+                material: material.clone(),
+            },
+            CustomUserEvent::SetMaterial(window, name, material) => Command::SetMaterial {
+                window: *window,
+                name: name.clone(),
+                material: material.clone(),
+            },
+            CustomUserEvent::DeleteMaterial(window, name) => Command::DeleteMaterial {
+                window: *window,
+                name: name.clone(),
+            },
+            CustomUserEvent::AssignMaterial(window, name, material) => Command::AssignMaterial {
+                window: *window,
+                name: name.clone(),
+                material: material.clone(),
+            },
+            CustomUserEvent::SetImageTint(window, name, color) => Command::SetImageTint {
+                window: *window,
+                name: name.clone(),
+                color: *color,
+            },
+            CustomUserEvent::SetLayer(window, name, order, ignore_depth) => Command::SetLayer {
+                window: *window,
+                name: name.clone(),
+                order: *order,
+                ignore_depth: *ignore_depth,
+            },
+            CustomUserEvent::DeleteLayer(window, name) => Command::DeleteLayer {
+                window: *window,
+                name: name.clone(),
+            },
+            CustomUserEvent::SetImageLayer(window, name, layer) => Command::SetImageLayer {
+                window: *window,
+                name: name.clone(),
+                layer: layer.clone(),
+            },
+            CustomUserEvent::SetImageZOrder(window, name, z_order) => Command::SetImageZOrder {
+                window: *window,
+                name: name.clone(),
+                z_order: *z_order,
+            },
+            CustomUserEvent::SetImageBlendMode(window, name, mode) => Command::SetImageBlendMode {
+                window: *window,
+                name: name.clone(),
+                mode: *mode,
+            },
+            CustomUserEvent::SetImageOpacity(window, name, opacity, duration) => {
+                Command::SetImageOpacity {
+                    window: *window,
+                    name: name.clone(),
+                    opacity: *opacity,
+                    duration: *duration,
+                }
+            }
+            CustomUserEvent::SetImageVisible(window, name, visible) => Command::SetImageVisible {
+                window: *window,
+                name: name.clone(),
+                visible: *visible,
+            },
+            CustomUserEvent::SetImageHitAlpha(window, name, threshold) => {
+                Command::SetImageHitAlpha {
+                    window: *window,
+                    name: name.clone(),
+                    threshold: *threshold,
+                }
+            }
+            CustomUserEvent::SetLayerVisible(window, name, visible) => Command::SetLayerVisible {
+                window: *window,
+                name: name.clone(),
+                visible: *visible,
+            },
+            CustomUserEvent::SetParent(window, child, parent) => Command::SetParent {
+                window: *window,
+                child: child.clone(),
+                parent: parent.clone(),
+            },
+            CustomUserEvent::DetachFromParent(window, child) => Command::DetachFromParent {
+                window: *window,
+                child: child.clone(),
+            },
+            CustomUserEvent::DeleteObject(window, name, recursive) => Command::DeleteObject {
+                window: *window,
+                name: name.clone(),
+                recursive: *recursive,
+            },
+            CustomUserEvent::SaveScene(window, path) => Command::SaveScene {
+                window: *window,
+                path: path.clone(),
+            },
+            CustomUserEvent::LoadScene(window, path) => Command::LoadScene {
+                window: *window,
+                path: path.clone(),
+            },
+            CustomUserEvent::SetClosePolicy(window, policy) => Command::SetClosePolicy {
+                window: *window,
+                policy: *policy,
+            },
+            CustomUserEvent::SetWindowPosition(window, x, y) => Command::SetWindowPosition {
+                window: *window,
+                x: *x,
+                y: *y,
+            },
+            CustomUserEvent::SetWindowSize(window, width, height) => Command::SetWindowSize {
+                window: *window,
+                width: *width,
+                height: *height,
+            },
+            CustomUserEvent::SetWindowTitle(window, title) => Command::SetWindowTitle {
+                window: *window,
+                title: title.clone(),
+            },
+            CustomUserEvent::SetFullscreen(window, mode) => Command::SetFullscreen {
+                window: *window,
+                mode: *mode,
+            },
+            CustomUserEvent::SetDecorations(window, decorations) => Command::SetDecorations {
+                window: *window,
+                decorations: *decorations,
+            },
+            CustomUserEvent::SetAlwaysOnTop(window, always_on_top) => Command::SetAlwaysOnTop {
+                window: *window,
+                always_on_top: *always_on_top,
+            },
+            CustomUserEvent::SetVisible(window, visible) => Command::SetVisible {
+                window: *window,
+                visible: *visible,
+            },
+            CustomUserEvent::SetCursorVisible(window, visible) => Command::SetCursorVisible {
+                window: *window,
+                visible: *visible,
+            },
+            CustomUserEvent::SetCursorIcon(window, icon) => Command::SetCursorIcon {
+                window: *window,
+                icon: icon.clone(),
+            },
+            CustomUserEvent::Minimize(window, minimized) => Command::Minimize {
+                window: *window,
+                minimized: *minimized,
+            },
+            CustomUserEvent::Maximize(window, maximized) => Command::Maximize {
+                window: *window,
+                maximized: *maximized,
+            },
+            CustomUserEvent::SetRedrawPolicy(window, policy) => Command::SetRedrawPolicy {
+                window: *window,
+                policy: *policy,
+            },
+        };
+        Some(command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples() -> Vec<Command> {
+        vec![
+            Command::CreateWindow {
+                id: 1,
+                width: 640,
+                height: 480,
+                title: "okno".to_string(),
+                background_color: [0.1, 0.2, 0.3, 1.0],
+                visible: false,
+            },
+            Command::DeleteWindow { id: 1 },
+            Command::AttachCanvas {
+                id: 2,
+                canvas: "game".to_string(),
+                background_color: [0.0, 0.0, 0.0, 0.5],
+            },
+            Command::AddImage {
+                window: 0,
+                name: "logo".to_string(),
+                texture: "assets/a.png".to_string(),
+                x: 1.0,
+                y: -2.0,
+                z: 0.5,
+            },
+            Command::SetImageSize {
+                window: 0,
+                name: "logo".to_string(),
+                width: 196,
+                height: 7000,
+            },
+            Command::PlayAnimation {
+                window: 0,
+                model: "robot".to_string(),
+                animation: "walk".to_string(),
+                speed: -1.5,
+                looping: true,
+            },
+            Command::AddLight {
+                window: 0,
+                name: "lamp".to_string(),
+                light: LightDescriptor::Spot {
+                    position: [0.0, 3.0, 0.0],
+                    direction: [0.0, -1.0, 0.0],
+                    inner_cutoff: 0.2,
+                    outer_cutoff: 0.4,
+                    color: [1.0, 0.9, 0.8, 1.0],
+                    intensity: 5.0,
+                    range: Some(20.0),
+                },
+            },
+            Command::EnableShadows {
+                window: 0,
+                light: "lamp".to_string(),
+                settings: ShadowSettings {
+                    resolution: 2048,
+                    ..Default::default()
+                },
+            },
+            Command::CreateMaterial {
+                window: 0,
+                name: "gold".to_string(),
+                material: MaterialDescriptor {
+                    albedo_texture: Some("assets/gold.png".to_string()),
+                    metallic: 1.0,
+                    ..Default::default()
+                },
+            },
+            Command::SetImageBlendMode {
+                window: 0,
+                name: "logo".to_string(),
+                mode: BlendMode::Multiply,
+            },
+            Command::SetImageOpacity {
+                window: 0,
+                name: "logo".to_string(),
+                opacity: 0.25,
+                duration: 1.5,
+            },
+            Command::DeleteObject {
+                window: 0,
+                name: "robot".to_string(),
+                recursive: true,
+            },
+            Command::SetClosePolicy {
+                window: 0,
+                policy: ClosePolicy::Notify,
+            },
+            Command::SetFullscreen {
+                window: 0,
+                mode: FullscreenMode::Borderless,
+            },
+            Command::SetWindowPosition {
+                window: 0,
+                x: -100,
+                y: 50,
+            },
+            Command::SetRedrawPolicy {
+                window: 0,
+                policy: RedrawPolicy::MaxFps(30),
+            },
+        ]
+    }
+
+    #[test]
+    fn command_round_trips_through_event() {
+        for command in samples() {
+            let event = command.clone().into_event();
+            assert_eq!(Command::from_event(&event), Some(command));
+        }
+    }
+
+    #[test]
+    fn command_round_trips_through_json_and_ron() {
+        for command in samples() {
+            let json = serde_json::to_string(&command).unwrap();
+            assert_eq!(serde_json::from_str::<Command>(&json).unwrap(), command);
+            let ron = ron::to_string(&command).unwrap();
+            assert_eq!(ron::from_str::<Command>(&ron).unwrap(), command);
+        }
+    }
+
+    #[test]
+    fn optional_fields_use_defaults() {
+        let command: Command = serde_json::from_str(
+            r#"{"CreateWindow": {"id": 0, "width": 800, "height": 600, "title": "t", "background_color": [0, 0, 0, 1]}}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            command,
+            Command::CreateWindow { visible: true, .. }
+        ));
+        let command: Command =
+            serde_json::from_str(r#"{"DeleteObject": {"window": 0, "name": "a"}}"#).unwrap();
+        assert!(matches!(
+            command,
+            Command::DeleteObject {
+                recursive: false,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn window_is_taken_from_id_or_window_field() {
+        assert_eq!(Command::DeleteWindow { id: 7 }.window(), 7);
+        assert_eq!(
+            Command::SetVisible {
+                window: 3,
+                visible: true
+            }
+            .window(),
+            3
+        );
+    }
+}
@@ -0,0 +1,104 @@
+//! HDR environment maps: skybox plus image-based lighting for PBR materials.
+use renderling::{atlas::AtlasImage, pbr::ibl::Ibl, skybox::Skybox};
+
+use crate::State;
+
+pub struct Environment {
+    pub hdr_path: String,
+    pub exposure: f32,
+    // obrót wokół osi Y w radianach
+    pub rotation: f32,
+    pub skybox: Skybox,
+    pub ibl: Ibl,
+}
+
+// Obrót mapy equirectangular wokół osi Y to przesunięcie kolumn obrazu
+fn rotate_equirectangular(img: &mut AtlasImage, angle_rad: f32) {
+    let width = img.size.x as usize;
+    let height = img.size.y as usize;
+    if width == 0 || height == 0 {
+        return;
+    }
+    let bytes_per_pixel = img.pixels.len() / (width * height);
+    let turns = (angle_rad / std::f32::consts::TAU).rem_euclid(1.0);
+    let shift = (turns * width as f32).round() as usize % width;
+    if shift == 0 {
+        return;
+    }
+    for row in img.pixels.chunks_exact_mut(width * bytes_per_pixel) {
+        row.rotate_right(shift * bytes_per_pixel);
+    }
+}
+
+impl State {
+    pub fn set_environment(&mut self, window_id: u32, hdr_path: &str) -> anyhow::Result<()> {
+        let ws = self
+            .windows
+            .get_mut(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+        let (exposure, rotation) = ws
+            .environment
+            .as_ref()
+            .map(|env| (env.exposure, env.rotation))
+            .unwrap_or((1.0, 0.0));
+
+        let mut hdr_img = AtlasImage::from_path(hdr_path)
+            .map_err(|e| anyhow::anyhow!("Failed to load HDR '{}': {:?}", hdr_path, e))?;
+        rotate_equirectangular(&mut hdr_img, rotation);
+
+        // skybox + mapy irradiance i prefiltered specular liczone z tego samego HDR
+        let skybox = Skybox::new(&ws.ctx, hdr_img);
+        let ibl = ws.stage.new_ibl(&skybox);
+        ws.stage.use_skybox(&skybox);
+        ws.stage.use_ibl(&ibl);
+        ws.stage.tonemapping().set_exposure(exposure);
+
+        ws.environment = Some(Environment {
+            hdr_path: hdr_path.to_string(),
+            exposure,
+            rotation,
+            skybox,
+            ibl,
+        });
+        log::info!("Environment '{}' set for window {}", hdr_path, window_id);
+        Ok(())
+    }
+
+    pub fn set_environment_exposure(
+        &mut self,
+        window_id: u32,
+        exposure: f32,
+    ) -> anyhow::Result<()> {
+        let ws = self
+            .windows
+            .get_mut(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+        let env = ws
+            .environment
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("Window {} has no environment", window_id))?;
+        env.exposure = exposure;
+        ws.stage.tonemapping().set_exposure(exposure);
+        Ok(())
+    }
+
+    pub fn set_environment_rotation(
+        &mut self,
+        window_id: u32,
+        angle_rad: f32,
+    ) -> anyhow::Result<()> {
+        let ws = self
+            .windows
+            .get_mut(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+        let env = ws
+            .environment
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("Window {} has no environment", window_id))?;
+        env.rotation = angle_rad;
+        let hdr_path = env.hdr_path.clone();
+
+        // obrócony HDR wymaga przeliczenia skyboxa i IBL
+        self.set_environment(window_id, &hdr_path)
+    }
+}
@@ -0,0 +1,322 @@
+//! Outbound events delivered to subscribers (protocol clients, JS callbacks, channels).
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PointerButton {
+    Left,
+    Right,
+    Middle,
+    Back,
+    Forward,
+    Other(u16),
+}
+
+impl From<winit::event::MouseButton> for PointerButton {
+    fn from(button: winit::event::MouseButton) -> Self {
+        match button {
+            winit::event::MouseButton::Left => Self::Left,
+            winit::event::MouseButton::Right => Self::Right,
+            winit::event::MouseButton::Middle => Self::Middle,
+            winit::event::MouseButton::Back => Self::Back,
+            winit::event::MouseButton::Forward => Self::Forward,
+            winit::event::MouseButton::Other(n) => Self::Other(n),
+        }
+    }
+}
+
+// `x`, `y` przy obrazkach to punkt na obrazku: (0, 0) w lewym górnym rogu, w jednostkach `size`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum OutboundEvent {
+    // `State` gotowy do przyjmowania komend
+    Ready,
+    ImageClicked {
+        window: u32,
+        name: String,
+        x: f32,
+        y: f32,
+        button: PointerButton,
+    },
+    ImageHoverEnter {
+        window: u32,
+        name: String,
+        x: f32,
+        y: f32,
+    },
+    ImageHoverLeave {
+        window: u32,
+        name: String,
+    },
+
+    // `key` to nazwa klawisza fizycznego z winit, np. "KeyW", "ArrowLeft"
+    Key {
+        window: u32,
+        key: String,
+        pressed: bool,
+        repeat: bool,
+    },
+    // pozycje kursora w pikselach logicznych
+    MouseMoved {
+        window: u32,
+        x: f32,
+        y: f32,
+    },
+    MouseButton {
+        window: u32,
+        button: PointerButton,
+        pressed: bool,
+    },
+    // w liniach (kółko) albo pikselach logicznych (touchpad)
+    MouseWheel {
+        window: u32,
+        dx: f32,
+        dy: f32,
+    },
+
+    // rozmiar w pikselach fizycznych
+    WindowResized {
+        window: u32,
+        width: u32,
+        height: u32,
+        scale_factor: f64,
+    },
+    WindowMoved {
+        window: u32,
+        x: i32,
+        y: i32,
+    },
+    WindowFocused {
+        window: u32,
+        focused: bool,
+    },
+    WindowCloseRequested {
+        window: u32,
+    },
+    // okno gotowe do przyjmowania komend
+    WindowOpened {
+        window: u32,
+    },
+    // okno usunięte – przez zamknięcie albo DeleteWindow
+    WindowClosed {
+        window: u32,
+    },
+
+    TextureLoaded {
+        window: u32,
+        path: String,
+    },
+    TextureFailed {
+        window: u32,
+        path: String,
+        error: String,
+    },
+
+    // po każdej wyrenderowanej klatce okna; `dt` w sekundach
+    FrameTick {
+        window: u32,
+        frame: u64,
+        dt: f32,
+    },
+}
+
+impl OutboundEvent {
+    // Okno, którego dotyczy zdarzenie; None = cała aplikacja
+    pub fn window(&self) -> Option<u32> {
+        match self {
+            OutboundEvent::Ready => None,
+            OutboundEvent::ImageClicked { window, .. }
+            | OutboundEvent::ImageHoverEnter { window, .. }
+            | OutboundEvent::ImageHoverLeave { window, .. }
+            | OutboundEvent::Key { window, .. }
+            | OutboundEvent::MouseMoved { window, .. }
+            | OutboundEvent::MouseButton { window, .. }
+            | OutboundEvent::MouseWheel { window, .. }
+            | OutboundEvent::WindowResized { window, .. }
+            | OutboundEvent::WindowMoved { window, .. }
+            | OutboundEvent::WindowFocused { window, .. }
+            | OutboundEvent::WindowCloseRequested { window }
+            | OutboundEvent::WindowOpened { window }
+            | OutboundEvent::WindowClosed { window }
+            | OutboundEvent::TextureLoaded { window, .. }
+            | OutboundEvent::TextureFailed { window, .. }
+            | OutboundEvent::FrameTick { window, .. } => Some(*window),
+        }
+    }
+
+    // Zdarzenia okna, które przekazujemy klientom; reszta zostaje w pętli zdarzeń
+    pub fn from_window_event(
+        window: u32,
+        scale_factor: f64,
+        event: &winit::event::WindowEvent,
+    ) -> Option<Self> {
+        use winit::event::{MouseScrollDelta, WindowEvent};
+
+        Some(match event {
+            WindowEvent::KeyboardInput { event, .. } => OutboundEvent::Key {
+                window,
+                key: match event.physical_key {
+                    winit::keyboard::PhysicalKey::Code(code) => format!("{:?}", code),
+                    winit::keyboard::PhysicalKey::Unidentified(code) => format!("{:?}", code),
+                },
+                pressed: event.state.is_pressed(),
+                repeat: event.repeat,
+            },
+            WindowEvent::CursorMoved { position, .. } => {
+                let position = position.to_logical::<f32>(scale_factor);
+                OutboundEvent::MouseMoved {
+                    window,
+                    x: position.x,
+                    y: position.y,
+                }
+            }
+            WindowEvent::MouseInput { state, button, .. } => OutboundEvent::MouseButton {
+                window,
+                button: (*button).into(),
+                pressed: state.is_pressed(),
+            },
+            WindowEvent::MouseWheel { delta, .. } => {
+                let (dx, dy) = match delta {
+                    MouseScrollDelta::LineDelta(dx, dy) => (*dx, *dy),
+                    MouseScrollDelta::PixelDelta(position) => {
+                        let position = position.to_logical::<f32>(scale_factor);
+                        (position.x, position.y)
+                    }
+                };
+                OutboundEvent::MouseWheel { window, dx, dy }
+            }
+            WindowEvent::Resized(size) => OutboundEvent::WindowResized {
+                window,
+                width: size.width,
+                height: size.height,
+                scale_factor,
+            },
+            WindowEvent::Moved(position) => OutboundEvent::WindowMoved {
+                window,
+                x: position.x,
+                y: position.y,
+            },
+            WindowEvent::Focused(focused) => OutboundEvent::WindowFocused {
+                window,
+                focused: *focused,
+            },
+            WindowEvent::CloseRequested => OutboundEvent::WindowCloseRequested { window },
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(pub u64);
+
+// Subskrybenci są wołani synchronicznie w pętli zdarzeń, więc nie powinni blokować;
+// mogą za to subskrybować i odsubskrybowywać w środku
+#[cfg(not(target_arch = "wasm32"))]
+pub type Subscriber = Box<dyn Fn(&OutboundEvent) + Send + Sync>;
+#[cfg(target_arch = "wasm32")]
+pub type Subscriber = Box<dyn Fn(&OutboundEvent)>;
+
+#[cfg(not(target_arch = "wasm32"))]
+type SharedSubscriber = std::sync::Arc<dyn Fn(&OutboundEvent) + Send + Sync>;
+#[cfg(target_arch = "wasm32")]
+type SharedSubscriber = std::rc::Rc<dyn Fn(&OutboundEvent)>;
+
+#[cfg(not(target_arch = "wasm32"))]
+static SUBSCRIBERS: std::sync::Mutex<Vec<(SubscriptionId, SharedSubscriber)>> =
+    std::sync::Mutex::new(Vec::new());
+
+#[cfg(target_arch = "wasm32")]
+thread_local! {
+    static SUBSCRIBERS: std::cell::RefCell<Vec<(SubscriptionId, SharedSubscriber)>> = std::cell::RefCell::new(Vec::new());
+}
+
+static NEXT_SUBSCRIPTION: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(1);
+
+pub fn subscribe(subscriber: Subscriber) -> SubscriptionId {
+    let id = next_subscription_id();
+    add_subscriber(id, subscriber);
+    id
+}
+
+fn next_subscription_id() -> SubscriptionId {
+    SubscriptionId(NEXT_SUBSCRIPTION.fetch_add(1, std::sync::atomic::Ordering::Relaxed))
+}
+
+fn add_subscriber(id: SubscriptionId, subscriber: Subscriber) {
+    #[cfg(not(target_arch = "wasm32"))]
+    SUBSCRIBERS.lock().unwrap().push((id, subscriber.into()));
+    #[cfg(target_arch = "wasm32")]
+    SUBSCRIBERS.with(|cell| cell.borrow_mut().push((id, subscriber.into())));
+}
+
+// Zdarzenia odbierane w dowolnym wątku, np. w pętli logiki gry klienta
+#[cfg(not(target_arch = "wasm32"))]
+pub fn subscribe_channel() -> (SubscriptionId, std::sync::mpsc::Receiver<OutboundEvent>) {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    // id znany z góry, żeby subskrybent mógł sam się wypisać
+    let id = next_subscription_id();
+    add_subscriber(
+        id,
+        Box::new(move |event| {
+            // odbiornik porzucony – subskrypcja nie jest już potrzebna
+            if sender.send(event.clone()).is_err() {
+                unsubscribe(id);
+            }
+        }),
+    );
+    (id, receiver)
+}
+
+pub fn unsubscribe(id: SubscriptionId) {
+    #[cfg(not(target_arch = "wasm32"))]
+    SUBSCRIBERS.lock().unwrap().retain(|(sub, _)| *sub != id);
+    #[cfg(target_arch = "wasm32")]
+    SUBSCRIBERS.with(|cell| cell.borrow_mut().retain(|(sub, _)| *sub != id));
+}
+
+pub fn emit(event: OutboundEvent) {
+    // kopia listy: subskrybent może (od)subskrybować bez zakleszczenia na blokadzie
+    #[cfg(not(target_arch = "wasm32"))]
+    let subscribers: Vec<SharedSubscriber> = SUBSCRIBERS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(_, subscriber)| subscriber.clone())
+        .collect();
+    #[cfg(target_arch = "wasm32")]
+    let subscribers: Vec<SharedSubscriber> = SUBSCRIBERS.with(|cell| {
+        cell.borrow()
+            .iter()
+            .map(|(_, subscriber)| subscriber.clone())
+            .collect()
+    });
+    for subscriber in subscribers {
+        subscriber(&event);
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+
+    fn is_subscribed(id: SubscriptionId) -> bool {
+        SUBSCRIBERS
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|(sub, _)| *sub == id)
+    }
+
+    #[test]
+    fn dropped_channel_receiver_unsubscribes() {
+        let (id, receiver) = subscribe_channel();
+        emit(OutboundEvent::WindowOpened { window: 7 });
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            OutboundEvent::WindowOpened { window: 7 }
+        );
+        assert!(is_subscribed(id));
+
+        drop(receiver);
+        emit(OutboundEvent::WindowOpened { window: 7 });
+        assert!(!is_subscribed(id));
+    }
+}
@@ -0,0 +1,107 @@
+//! GPU device and queue shared by all windows of a `State`; surfaces, stages and texture atlases stay per window, since each renderling stage owns its atlas.
+use std::sync::{Arc, Mutex};
+
+use renderling::{context::RenderTarget, Context};
+
+// Te same cechy, o które prosi `Context` renderlinga przy tworzeniu własnego urządzenia –
+// bez nich pipeline'y stage'y mogą nie powstać albo działać inaczej
+const RENDERLING_FEATURES: wgpu::Features = wgpu::Features::INDIRECT_FIRST_INSTANCE
+    .union(wgpu::Features::MULTI_DRAW_INDIRECT)
+    .union(wgpu::Features::VERTEX_WRITABLE_STORAGE)
+    .union(wgpu::Features::CLEAR_TEXTURE);
+
+// renderling bierze tylko cechy obsługiwane przez adapter, resztę zastępuje wolniejszą ścieżką
+fn renderling_features(adapter: &wgpu::Adapter) -> wgpu::Features {
+    RENDERLING_FEATURES.intersection(adapter.features())
+}
+
+#[derive(Clone)]
+struct Device {
+    adapter: Arc<wgpu::Adapter>,
+    device: Arc<wgpu::Device>,
+    queue: Arc<wgpu::Queue>,
+}
+
+// Jedno urządzenie na całą aplikację – każde okno dostaje tylko własną powierzchnię (surface).
+// Adapter wybieramy dopiero przy pierwszym oknie, żeby pasował do jego powierzchni
+#[derive(Clone)]
+pub struct SharedGpu {
+    instance: Arc<wgpu::Instance>,
+    device: Arc<Mutex<Option<Device>>>,
+}
+
+impl Default for SharedGpu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SharedGpu {
+    pub fn new() -> Self {
+        Self {
+            instance: Arc::new(wgpu::Instance::new(&wgpu::InstanceDescriptor::default())),
+            device: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    async fn request_device(&self, surface: &wgpu::Surface<'_>) -> anyhow::Result<Device> {
+        let adapter = self
+            .instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                compatible_surface: Some(surface),
+                force_fallback_adapter: false,
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!("No GPU adapter: {}", e))?;
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor {
+                label: Some("renderling_test"),
+                required_features: renderling_features(&adapter),
+                required_limits: adapter.limits(),
+                ..Default::default()
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to create GPU device: {}", e))?;
+        log::info!("created shared GPU device: {:?}", adapter.get_info());
+        Ok(Device {
+            adapter: Arc::new(adapter),
+            device: Arc::new(device),
+            queue: Arc::new(queue),
+        })
+    }
+
+    // Tworzy tylko surface okna; urządzenie i kolejka są wspólne
+    pub async fn context_for(&self, window: Arc<winit::window::Window>) -> anyhow::Result<Context> {
+        let size = window.inner_size();
+        let surface = self.instance.create_surface(window)?;
+        let existing = self.device.lock().unwrap().clone();
+        let gpu = match existing {
+            Some(gpu) => gpu,
+            None => {
+                let created = self.request_device(&surface).await?;
+                // inne okno mogło utworzyć urządzenie w trakcie `await` – wygrywa pierwsze
+                self.device.lock().unwrap().get_or_insert(created).clone()
+            }
+        };
+        // adapter wybrano dla powierzchni pierwszego okna – kolejne mogą go nie obsługiwać
+        if surface.get_capabilities(&gpu.adapter).formats.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Window surface is not supported by the shared GPU adapter {:?}",
+                gpu.adapter.get_info().name
+            ));
+        }
+        let config = surface
+            .get_default_config(&gpu.adapter, size.width.max(1), size.height.max(1))
+            .ok_or_else(|| {
+                anyhow::anyhow!("Window surface is not supported by the shared GPU adapter")
+            })?;
+        surface.configure(&gpu.device, &config);
+        Ok(Context::new(
+            RenderTarget::from_surface(surface, config),
+            gpu.adapter,
+            gpu.device,
+            gpu.queue,
+        ))
+    }
+}
@@ -0,0 +1,427 @@
+//! JavaScript API for the wasm build: every call sends a command and returns a Promise.
+use wasm_bindgen::{prelude::*, JsCast};
+
+use crate::{Command, CustomUserEvent, Reply, Responder};
+
+#[wasm_bindgen(typescript_custom_section)]
+const TS_TYPES: &str = r#"
+export type BlendMode = "Alpha" | "Premultiplied" | "Additive" | "Multiply" | "Screen";
+
+export type FullscreenMode = "Off" | "Borderless" | "Exclusive";
+
+export type ClosePolicy = "ExitApp" | "CloseWindow" | "Notify";
+
+export type RedrawPolicy = "Continuous" | "OnDemand" | { MaxFps: number };
+
+export type Rgba = [number, number, number, number];
+export type Vec3 = [number, number, number];
+
+export type LightDescriptor =
+  | { Directional: { direction: Vec3; color: Rgba; intensity: number } }
+  | { Point: { position: Vec3; color: Rgba; intensity: number } }
+  | { Spot: { position: Vec3; direction: Vec3; inner_cutoff: number; outer_cutoff: number; color: Rgba; intensity: number; range?: number } };
+
+// pominięte pola przyjmują wartości domyślne
+export interface ShadowSettings {
+  resolution?: number;
+  bias_min?: number;
+  bias_max?: number;
+  pcf_samples?: number;
+}
+
+// pominięte pola przyjmują wartości domyślne
+export interface MaterialDescriptor {
+  albedo_color?: Rgba;
+  albedo_texture?: string | null;
+  metallic?: number;
+  roughness?: number;
+  emissive?: Vec3;
+  emissive_strength?: number;
+  normal_map?: string | null;
+  occlusion_map?: string | null;
+  occlusion_strength?: number;
+  double_sided?: boolean;
+  unlit?: boolean;
+}
+
+export type Command =
+  | { CreateWindow: { id: number; width: number; height: number; title: string; background_color: [number, number, number, number]; visible?: boolean } }
+  | { DeleteWindow: { id: number } }
+  | { AttachCanvas: { id: number; canvas: string; background_color: [number, number, number, number] } }
+  | { LoadTexture: { window: number; path: string } }
+  | { AddImage: { window: number; name: string; texture: string; x: number; y: number; z: number } }
+  | { SetImagePosition: { window: number; name: string; x: number; y: number; z: number } }
+  | { SetImageSize: { window: number; name: string; width: number; height: number } }
+  | { SetImageRotation: { window: number; name: string; angle: number } }
+  | { DeleteImage: { window: number; name: string } }
+  | { DeleteTexture: { window: number; path: string } }
+  | { LoadModel: { window: number; name: string; path: string; x: number; y: number; z: number } }
+  | { SetModelPosition: { window: number; name: string; x: number; y: number; z: number } }
+  | { SetModelRotation: { window: number; name: string; x: number; y: number; z: number } }
+  | { SetModelScale: { window: number; name: string; x: number; y: number; z: number } }
+  | { DeleteModel: { window: number; name: string } }
+  | { ListAnimations: { window: number; model: string } }
+  | { PlayAnimation: { window: number; model: string; animation: string; speed: number; looping: boolean } }
+  | { PauseAnimation: { window: number; model: string; animation: string } }
+  | { StopAnimation: { window: number; model: string; animation: string } }
+  | { SeekAnimation: { window: number; model: string; animation: string; time: number } }
+  | { SetAnimationWeight: { window: number; model: string; animation: string; weight: number } }
+  | { SetLighting: { window: number; enabled: boolean } }
+  | { AddLight: { window: number; name: string; light: LightDescriptor } }
+  | { SetLight: { window: number; name: string; light: LightDescriptor } }
+  | { DeleteLight: { window: number; name: string } }
+  | { EnableShadows: { window: number; light: string; settings?: ShadowSettings } }
+  | { DisableShadows: { window: number; light: string } }
+  | { SetCastShadows: { window: number; name: string; cast: boolean } }
+  | { SetEnvironment: { window: number; hdr_path: string } }
+  | { SetEnvironmentExposure: { window: number; exposure: number } }
+  | { SetEnvironmentRotation: { window: number; angle: number } }
+  | { CreateMaterial: { window: number; name: string; material?: MaterialDescriptor } }
+  | { SetMaterial: { window: number; name: string; material: MaterialDescriptor } }
+  | { DeleteMaterial: { window: number; name: string } }
+  | { AssignMaterial: { window: number; name: string; material: string } }
+  | { SetImageTint: { window: number; name: string; color: [number, number, number, number] } }
+  | { SetLayer: { window: number; name: string; order: number; ignore_depth: boolean } }
+  | { DeleteLayer: { window: number; name: string } }
+  | { SetImageLayer: { window: number; name: string; layer: string } }
+  | { SetImageZOrder: { window: number; name: string; z_order: number } }
+  | { SetImageBlendMode: { window: number; name: string; mode: BlendMode } }
+  | { SetImageOpacity: { window: number; name: string; opacity: number; duration?: number } }
+  | { SetImageVisible: { window: number; name: string; visible: boolean } }
+  | { SetLayerVisible: { window: number; name: string; visible: boolean } }
+  | { SetImageHitAlpha: { window: number; name: string; threshold: number } }
+  | { SetParent: { window: number; child: string; parent: string } }
+  | { DetachFromParent: { window: number; child: string } }
+  | { DeleteObject: { window: number; name: string; recursive?: boolean } }
+  | { SaveScene: { window: number; path: string } }
+  | { LoadScene: { window: number; path: string } }
+  | { SetClosePolicy: { window: number; policy: ClosePolicy } }
+  | { SetWindowPosition: { window: number; x: number; y: number } }
+  | { SetWindowSize: { window: number; width: number; height: number } }
+  | { SetWindowTitle: { window: number; title: string } }
+  | { SetFullscreen: { window: number; mode: FullscreenMode } }
+  | { SetDecorations: { window: number; decorations: boolean } }
+  | { SetAlwaysOnTop: { window: number; always_on_top: boolean } }
+  | { SetVisible: { window: number; visible: boolean } }
+  | { SetCursorVisible: { window: number; visible: boolean } }
+  | { SetCursorIcon: { window: number; icon: string } }
+  | { Minimize: { window: number; minimized: boolean } }
+  | { Maximize: { window: number; maximized: boolean } }
+  | { SetRedrawPolicy: { window: number; policy: RedrawPolicy } };
+
+export type PointerButton = "Left" | "Right" | "Middle" | "Back" | "Forward" | { Other: number };
+
+export type OutboundEvent =
+  | "Ready"
+  | { ImageClicked: { window: number; name: string; x: number; y: number; button: PointerButton } }
+  | { ImageHoverEnter: { window: number; name: string; x: number; y: number } }
+  | { ImageHoverLeave: { window: number; name: string } }
+  | { Key: { window: number; key: string; pressed: boolean; repeat: boolean } }
+  | { MouseMoved: { window: number; x: number; y: number } }
+  | { MouseButton: { window: number; button: PointerButton; pressed: boolean } }
+  | { MouseWheel: { window: number; dx: number; dy: number } }
+  | { WindowResized: { window: number; width: number; height: number; scale_factor: number } }
+  | { WindowMoved: { window: number; x: number; y: number } }
+  | { WindowFocused: { window: number; focused: boolean } }
+  | { WindowCloseRequested: { window: number } }
+  | { WindowOpened: { window: number } }
+  | { WindowClosed: { window: number } }
+  | { TextureLoaded: { window: number; path: string } }
+  | { TextureFailed: { window: number; path: string; error: string } }
+  | { FrameTick: { window: number; frame: number; dt: number } };
+
+export interface Reply {
+  id?: number;
+  ok: boolean;
+  result?: unknown;
+  error?: string;
+}
+"#;
+
+// Promise spełnia się wynikiem komendy (albo `undefined`), a odrzuca błędem z `State`
+fn send(command: Command) -> js_sys::Promise {
+    js_sys::Promise::new(&mut |resolve, reject| {
+        let Some(proxy) = crate::get_global_proxy() else {
+            let _ = reject.call1(
+                &JsValue::NULL,
+                &js_sys::Error::new("Event loop proxy is not set"),
+            );
+            return;
+        };
+        let on_closed = reject.clone();
+        let responder: Responder = Box::new(move |reply: Reply| {
+            let _ = match reply_to_js(reply) {
+                Ok(value) => resolve.call1(&JsValue::NULL, &value),
+                Err(error) => reject.call1(&JsValue::NULL, &error),
+            };
+        });
+        if proxy
+            .send_event(CustomUserEvent::Command(command.clone(), Some(responder)))
+            .is_err()
+        {
+            let _ = on_closed.call1(&JsValue::NULL, &js_sys::Error::new("Event loop is closed"));
+        }
+    })
+}
+
+fn reply_to_js(reply: Reply) -> Result<JsValue, JsValue> {
+    if !reply.ok {
+        let error = reply.error.unwrap_or_default();
+        return Err(js_sys::Error::new(&error).into());
+    }
+    match reply.result {
+        Some(value) => js_sys::JSON::parse(&value.to_string()),
+        None => Ok(JsValue::UNDEFINED),
+    }
+}
+
+// Zwraca id subskrypcji dla `offEvent`
+#[wasm_bindgen(js_name = onEvent)]
+pub fn on_event(
+    #[wasm_bindgen(unchecked_param_type = "(event: OutboundEvent) => void")]
+    callback: js_sys::Function,
+) -> f64 {
+    let id = crate::subscribe(Box::new(move |event| {
+        let value = serde_json::to_string(event)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+            .and_then(|text| js_sys::JSON::parse(&text));
+        let result = value.and_then(|value| callback.call1(&JsValue::NULL, &value));
+        if let Err(e) = result {
+            log::error!("Event callback failed: {:?}", e);
+        }
+    }));
+    id.0 as f64
+}
+
+#[wasm_bindgen(js_name = offEvent)]
+pub fn off_event(id: f64) {
+    crate::unsubscribe(crate::SubscriptionId(id as u64));
+}
+
+// Dowolna komenda w formacie protokołu, np. `{ DeleteImage: { window: 0, name: "logo" } }`
+#[wasm_bindgen(js_name = sendCommand, unchecked_return_type = "Promise<unknown>")]
+pub fn send_command(
+    #[wasm_bindgen(unchecked_param_type = "Command")] command: JsValue,
+) -> js_sys::Promise {
+    let command = js_sys::JSON::stringify(&command)
+        .map(String::from)
+        .map_err(|e| format!("{:?}", e))
+        .and_then(|text| serde_json::from_str::<Command>(&text).map_err(|e| e.to_string()));
+    match command {
+        Ok(command) => send(command),
+        Err(e) => js_sys::Promise::reject(&js_sys::Error::new(&format!("Invalid command: {}", e))),
+    }
+}
+
+// Kolor tła jako [r, g, b, a] w zakresie 0..1; domyślnie ten sam co okna 0
+fn background_color(background: Option<Vec<f32>>) -> [f32; 4] {
+    match background.as_deref() {
+        Some([r, g, b, a]) => [*r, *g, *b, *a],
+        Some([r, g, b]) => [*r, *g, *b, 1.0],
+        _ => [0.1, 0.2, 0.3, 1.0],
+    }
+}
+
+// Nowy canvas dopisany na końcu <body>
+#[wasm_bindgen(js_name = createWindow, unchecked_return_type = "Promise<void>")]
+pub fn create_window(
+    id: u32,
+    width: u32,
+    height: u32,
+    title: String,
+    background: Option<Vec<f32>>,
+) -> js_sys::Promise {
+    send(Command::CreateWindow {
+        id,
+        width,
+        height,
+        title,
+        background_color: background_color(background),
+        visible: true,
+    })
+}
+
+// `canvas` to id elementu albo sam element
+#[wasm_bindgen(js_name = attachCanvas, unchecked_return_type = "Promise<void>")]
+pub fn attach_canvas(
+    id: u32,
+    #[wasm_bindgen(unchecked_param_type = "string | HTMLCanvasElement")] canvas: JsValue,
+    background: Option<Vec<f32>>,
+) -> js_sys::Promise {
+    let canvas = match canvas.as_string() {
+        Some(canvas_id) => canvas_id,
+        None => match canvas.dyn_into::<web_sys::HtmlCanvasElement>() {
+            Ok(canvas) => crate::canvas::canvas_id(&canvas, id),
+            Err(_) => {
+                return js_sys::Promise::reject(&js_sys::Error::new(
+                    "Expected a canvas id or an HTMLCanvasElement",
+                ))
+            }
+        },
+    };
+    send(Command::AttachCanvas {
+        id,
+        canvas,
+        background_color: background_color(background),
+    })
+}
+
+#[wasm_bindgen(js_name = deleteWindow, unchecked_return_type = "Promise<void>")]
+pub fn delete_window(id: u32) -> js_sys::Promise {
+    send(Command::DeleteWindow { id })
+}
+
+#[wasm_bindgen(js_name = loadTexture, unchecked_return_type = "Promise<void>")]
+pub fn load_texture(window: u32, path: String) -> js_sys::Promise {
+    send(Command::LoadTexture { window, path })
+}
+
+#[wasm_bindgen(js_name = deleteTexture, unchecked_return_type = "Promise<void>")]
+pub fn delete_texture(window: u32, path: String) -> js_sys::Promise {
+    send(Command::DeleteTexture { window, path })
+}
+
+#[wasm_bindgen(js_name = addImage, unchecked_return_type = "Promise<void>")]
+pub fn add_image(
+    window: u32,
+    name: String,
+    texture: String,
+    x: f32,
+    y: f32,
+    z: f32,
+) -> js_sys::Promise {
+    send(Command::AddImage {
+        window,
+        name,
+        texture,
+        x,
+        y,
+        z,
+    })
+}
+
+#[wasm_bindgen(js_name = setImagePosition, unchecked_return_type = "Promise<void>")]
+pub fn set_image_position(window: u32, name: String, x: f32, y: f32, z: f32) -> js_sys::Promise {
+    send(Command::SetImagePosition {
+        window,
+        name,
+        x,
+        y,
+        z,
+    })
+}
+
+#[wasm_bindgen(js_name = setImageSize, unchecked_return_type = "Promise<void>")]
+pub fn set_image_size(window: u32, name: String, width: u32, height: u32) -> js_sys::Promise {
+    send(Command::SetImageSize {
+        window,
+        name,
+        width,
+        height,
+    })
+}
+
+#[wasm_bindgen(js_name = setImageRotation, unchecked_return_type = "Promise<void>")]
+pub fn set_image_rotation(window: u32, name: String, angle: f32) -> js_sys::Promise {
+    send(Command::SetImageRotation {
+        window,
+        name,
+        angle,
+    })
+}
+
+#[wasm_bindgen(js_name = setImageVisible, unchecked_return_type = "Promise<void>")]
+pub fn set_image_visible(window: u32, name: String, visible: bool) -> js_sys::Promise {
+    send(Command::SetImageVisible {
+        window,
+        name,
+        visible,
+    })
+}
+
+// `duration` w sekundach; 0 = od razu
+#[wasm_bindgen(js_name = setImageOpacity, unchecked_return_type = "Promise<void>")]
+pub fn set_image_opacity(
+    window: u32,
+    name: String,
+    opacity: f32,
+    duration: f32,
+) -> js_sys::Promise {
+    send(Command::SetImageOpacity {
+        window,
+        name,
+        opacity,
+        duration,
+    })
+}
+
+#[wasm_bindgen(js_name = deleteImage, unchecked_return_type = "Promise<void>")]
+pub fn delete_image(window: u32, name: String) -> js_sys::Promise {
+    send(Command::DeleteImage { window, name })
+}
+
+#[wasm_bindgen(js_name = loadModel, unchecked_return_type = "Promise<void>")]
+pub fn load_model(
+    window: u32,
+    name: String,
+    path: String,
+    x: f32,
+    y: f32,
+    z: f32,
+) -> js_sys::Promise {
+    send(Command::LoadModel {
+        window,
+        name,
+        path,
+        x,
+        y,
+        z,
+    })
+}
+
+#[wasm_bindgen(js_name = setModelPosition, unchecked_return_type = "Promise<void>")]
+pub fn set_model_position(window: u32, name: String, x: f32, y: f32, z: f32) -> js_sys::Promise {
+    send(Command::SetModelPosition {
+        window,
+        name,
+        x,
+        y,
+        z,
+    })
+}
+
+#[wasm_bindgen(js_name = deleteModel, unchecked_return_type = "Promise<void>")]
+pub fn delete_model(window: u32, name: String) -> js_sys::Promise {
+    send(Command::DeleteModel { window, name })
+}
+
+#[wasm_bindgen(js_name = listAnimations, unchecked_return_type = "Promise<string[]>")]
+pub fn list_animations(window: u32, model: String) -> js_sys::Promise {
+    send(Command::ListAnimations { window, model })
+}
+
+#[wasm_bindgen(js_name = playAnimation, unchecked_return_type = "Promise<void>")]
+pub fn play_animation(
+    window: u32,
+    model: String,
+    animation: String,
+    speed: f32,
+    looping: bool,
+) -> js_sys::Promise {
+    send(Command::PlayAnimation {
+        window,
+        model,
+        animation,
+        speed,
+        looping,
+    })
+}
+
+#[wasm_bindgen(js_name = stopAnimation, unchecked_return_type = "Promise<void>")]
+pub fn stop_animation(window: u32, model: String, animation: String) -> js_sys::Promise {
+    send(Command::StopAnimation {
+        window,
+        model,
+        animation,
+    })
+}
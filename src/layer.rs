@@ -0,0 +1,492 @@
+//! Render layers, z-ordering and draw sorting for 2D sprites.
+use craballoc::value::Hybrid;
+use renderling::{
+    atlas::AtlasTexture,
+    camera::Camera,
+    stage::{Renderlet, Stage},
+    Context,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    blend::{load_atlas_image, texture_key, BlendLayer},
+    BlendMode, ImageObject, State, WindowState,
+};
+
+pub const DEFAULT_LAYER: &str = "default";
+
+// Warstwy z głębią dzielą główny stage z modelami i test głębi rozstrzyga, co jest na wierzchu;
+// `order` i z-order ustalają tam tylko kolejność rysowania (mieszanie półprzezroczystych).
+// Jawną kolejność dają warstwy `ignore_depth`: każda para (warstwa, z-order) ma własny stage
+// rysowany na klatkę osobnym przebiegiem, rosnąco, więc nie zależy od precyzji bufora głębi
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RenderLayer {
+    pub order: i32,
+    // warstwy ignorujące głębię (np. UI) rysujemy osobnymi przebiegami na wierzchu
+    pub ignore_depth: bool,
+    pub visible: bool,
+}
+
+impl Default for RenderLayer {
+    fn default() -> Self {
+        Self {
+            order: 0,
+            ignore_depth: false,
+            visible: true,
+        }
+    }
+}
+
+// Przebieg overlay: rysowane rosnąco wg porządku warstwy, potem z-order obrazka
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct OverlayKey {
+    pub layer_order: i32,
+    pub z_order: i32,
+}
+
+// Stage, na którym leży renderlet obrazka
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImageStage {
+    Main,
+    // warstwy ignorujące głębię
+    Overlay(OverlayKey),
+    // tryby mieszania nakładane na klatkę osobnym przebiegiem
+    Blend(BlendMode),
+}
+
+// Tryb mieszania ma pierwszeństwo przed warstwą
+fn stage_for(blend: BlendMode, ignore_depth: bool, layer_order: i32, z_order: i32) -> ImageStage {
+    if blend.is_composited() {
+        ImageStage::Blend(blend)
+    } else if ignore_depth {
+        ImageStage::Overlay(OverlayKey {
+            layer_order,
+            z_order,
+        })
+    } else {
+        ImageStage::Main
+    }
+}
+
+// Stage jednego przebiegu overlay – własny bufor głębi czyszczony co klatkę, kolor z klatki
+pub struct OverlayPass {
+    pub stage: Stage,
+    pub camera: Hybrid<Camera>,
+    pub tex: std::collections::HashMap<String, Hybrid<AtlasTexture>>,
+}
+
+impl OverlayPass {
+    pub fn new(ctx: &Context, camera: Camera) -> Self {
+        let stage = ctx
+            .new_stage()
+            .with_lighting(false)
+            .with_size(ctx.get_size());
+        stage.set_clear_color_attachments(false);
+        let camera = stage.new_value(camera);
+        Self {
+            stage,
+            camera,
+            tex: std::collections::HashMap::new(),
+        }
+    }
+}
+
+impl ImageObject {
+    pub fn center(&self) -> glam::Vec3 {
+        let mut center = glam::Vec3::ZERO;
+        for v in &self.vertices_cpu {
+            center += v.position;
+        }
+        center / self.vertices_cpu.len().max(1) as f32
+    }
+}
+
+impl WindowState {
+    pub fn layer_order(&self, layer: &str) -> i32 {
+        self.layers.get(layer).map(|l| l.order).unwrap_or(0)
+    }
+
+    fn layer_ignores_depth(&self, layer: &str) -> bool {
+        self.layers.get(layer).is_some_and(|l| l.ignore_depth)
+    }
+
+    // Obrazek jest rysowany, gdy widoczny jest on sam i jego warstwa
+    pub fn is_image_visible(&self, image: &ImageObject) -> bool {
+        image.visible && self.layers.get(&image.layer).map_or(true, |l| l.visible)
+    }
+
+    // Renderlet zostaje na stage, tylko nie jest rysowany
+    fn update_visibility(&self, name: &str) {
+        let Some(image) = self.images.get(name) else {
+            return;
+        };
+        let visible = self.is_image_visible(image);
+        let mut renderlet = image.renderlet.get();
+        if renderlet.visible != visible {
+            renderlet.visible = visible;
+            image.renderlet.set(renderlet);
+        }
+    }
+
+    // Kolejność rysowania: warstwa, z-order, a potem od najdalszego do najbliższego,
+    // żeby półprzezroczyste obrazki mieszały się poprawnie
+    pub fn sort_images(&mut self) {
+        let eye = self.camera.view().inverse().col(3).truncate();
+        let mut order: Vec<(&String, &ImageObject)> = self.images.iter().collect();
+        order.sort_by(|(a_name, a), (b_name, b)| {
+            let a_key = (self.layer_order(&a.layer), a.z_order);
+            let b_key = (self.layer_order(&b.layer), b.z_order);
+            a_key
+                .cmp(&b_key)
+                .then_with(|| {
+                    let a_dist = a.center().distance_squared(eye);
+                    let b_dist = b.center().distance_squared(eye);
+                    b_dist.total_cmp(&a_dist)
+                })
+                .then_with(|| a_name.cmp(b_name))
+        });
+
+        let names: Vec<String> = order.iter().map(|(name, _)| (*name).clone()).collect();
+        if names == self.draw_order {
+            return;
+        }
+        for (_, image) in &order {
+            self.stage_of(image.stage)
+                .remove_renderlet(&image.renderlet);
+        }
+        for (_, image) in &order {
+            self.stage_of(image.stage).add_renderlet(&image.renderlet);
+        }
+        self.draw_order = names;
+    }
+
+    pub fn stage_of(&self, stage: ImageStage) -> &Stage {
+        match stage {
+            ImageStage::Main => &self.stage,
+            ImageStage::Overlay(key) => &self.overlays[&key].stage,
+            ImageStage::Blend(mode) => &self.blend_layers[&mode].stage,
+        }
+    }
+
+    fn target_stage(&self, image: &ImageObject) -> ImageStage {
+        stage_for(
+            image.blend,
+            self.layer_ignores_depth(&image.layer),
+            self.layer_order(&image.layer),
+            image.z_order,
+        )
+    }
+
+    // Przebiegi overlay bez obrazków tylko zajmowałyby pamięć GPU
+    fn drop_unused_overlays(&mut self) {
+        let used: std::collections::HashSet<ImageStage> =
+            self.images.values().map(|image| image.stage).collect();
+        self.overlays
+            .retain(|key, _| used.contains(&ImageStage::Overlay(*key)));
+    }
+
+    // Każdy stage ma własny atlas, więc tekstura musi zostać załadowana osobno dla każdego
+    pub fn stage_texture(
+        &mut self,
+        target: ImageStage,
+        path: &str,
+        blend: BlendMode,
+    ) -> anyhow::Result<Hybrid<AtlasTexture>> {
+        match target {
+            ImageStage::Blend(mode) => {
+                self.blend_layers
+                    .entry(mode)
+                    .or_insert_with(|| BlendLayer::new(&self.ctx, mode, self.camera));
+            }
+            ImageStage::Overlay(key) => {
+                self.overlays
+                    .entry(key)
+                    .or_insert_with(|| OverlayPass::new(&self.ctx, self.camera));
+            }
+            ImageStage::Main => {}
+        }
+        let (stage, tex) = match target {
+            ImageStage::Main => (&self.stage, &mut self.tex),
+            ImageStage::Overlay(key) => {
+                let pass = self.overlays.get_mut(&key).unwrap();
+                (&pass.stage, &mut pass.tex)
+            }
+            ImageStage::Blend(mode) => {
+                let layer = self.blend_layers.get_mut(&mode).unwrap();
+                (&layer.stage, &mut layer.tex)
+            }
+        };
+        let key = texture_key(path, blend);
+        if let Some(texture) = tex.get(&key) {
+            return Ok(texture.clone());
+        }
+        let atlas_image = load_atlas_image(&key)?;
+        let texture = stage.add_images([atlas_image])?[0].clone();
+        tex.insert(key, texture.clone());
+        Ok(texture)
+    }
+
+    // Przenosi obrazek na stage wynikający z warstwy i trybu mieszania albo podmienia mu
+    // teksturę na wersję dla nowego trybu. Materiał (i przy zmianie stage renderlet)
+    // muszą powstać od nowa w slabie docelowego stage
+    pub fn rebind_image(&mut self, name: &str) -> anyhow::Result<()> {
+        let image = self
+            .images
+            .get(name)
+            .ok_or_else(|| anyhow::anyhow!("No image with name '{}'", name))?;
+        let target = self.target_stage(image);
+        let (path, blend) = (image.texture_path.clone(), image.blend);
+        let texture = self.stage_texture(target, &path, blend)?;
+
+        let image = &self.images[name];
+        let same_stage = image.stage == target;
+        let mut mat = image.material.get();
+        if same_stage && mat.albedo_texture_id == texture.id() {
+            return Ok(());
+        }
+        mat.albedo_texture_id = texture.id();
+        if !same_stage {
+            // tekstury normalnych/AO z nazwanego materiału nie istnieją w drugim atlasie
+            mat.normal_texture_id = Default::default();
+            mat.ao_texture_id = Default::default();
+        }
+        let material = self.stage_of(target).new_material(mat);
+
+        if same_stage {
+            let image = self.images.get_mut(name).unwrap();
+            let mut renderlet = image.renderlet.get();
+            renderlet.material_id = material.id();
+            image.renderlet.set(renderlet);
+            image.material = material;
+            return self.reapply_named_material(name);
+        }
+
+        let renderlet = self.stage_of(target).new_renderlet(Renderlet {
+            vertices_array: image.vertices.array(),
+            material_id: material.id(),
+            camera_id: self.stage_camera(target).id(),
+            visible: self.is_image_visible(image),
+            ..Default::default()
+        });
+        self.stage_of(image.stage)
+            .remove_renderlet(&image.renderlet);
+
+        let image = self.images.get_mut(name).unwrap();
+        image.renderlet = renderlet;
+        image.material = material;
+        image.stage = target;
+        self.upload_image(name);
+        self.stage_of(target)
+            .add_renderlet(&self.images[name].renderlet);
+        self.drop_unused_overlays();
+
+        // wymusza ponowne ułożenie kolejności
+        self.draw_order.clear();
+        self.reapply_named_material(name)
+    }
+
+    // Po zmianie stage'a albo trybu mieszania materiał nazwany wskazuje na tekstury z innego
+    // atlasu – nakładamy go ponownie na nowy materiał obrazka
+    fn reapply_named_material(&mut self, name: &str) -> anyhow::Result<()> {
+        match self.images[name].material_name.clone() {
+            Some(material_name) => self.apply_named_material(name, &material_name),
+            None => Ok(()),
+        }
+    }
+
+    fn refresh_layer_images(&mut self, layer: &str) -> anyhow::Result<()> {
+        let names: Vec<String> = self
+            .images
+            .iter()
+            .filter(|(_, image)| image.layer == layer)
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in names {
+            self.rebind_image(&name)?;
+            self.update_visibility(&name);
+        }
+        self.sort_images();
+        Ok(())
+    }
+}
+
+impl State {
+    // Tworzy warstwę albo zmienia istniejącą
+    pub fn set_layer(
+        &mut self,
+        window_id: u32,
+        name: String,
+        order: i32,
+        ignore_depth: bool,
+    ) -> anyhow::Result<()> {
+        let ws = self
+            .windows
+            .get_mut(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+        // widoczność zostaje bez zmian – od niej jest SetLayerVisible
+        let layer = ws.layers.entry(name.clone()).or_default();
+        layer.order = order;
+        layer.ignore_depth = ignore_depth;
+        ws.refresh_layer_images(&name)
+    }
+
+    pub fn delete_layer(&mut self, window_id: u32, name: &str) -> anyhow::Result<()> {
+        if name == DEFAULT_LAYER {
+            return Err(anyhow::anyhow!("Default layer can't be deleted"));
+        }
+        let ws = self
+            .windows
+            .get_mut(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+        ws.layers
+            .remove(name)
+            .ok_or_else(|| anyhow::anyhow!("No layer with name '{}'", name))?;
+        // obrazki wracają do warstwy domyślnej
+        for image in ws.images.values_mut() {
+            if image.layer == name {
+                image.layer = DEFAULT_LAYER.to_string();
+            }
+        }
+        ws.refresh_layer_images(DEFAULT_LAYER)
+    }
+
+    pub fn set_image_layer(
+        &mut self,
+        window_id: u32,
+        name: &str,
+        layer: &str,
+    ) -> anyhow::Result<()> {
+        let ws = self
+            .windows
+            .get_mut(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+        if !ws.layers.contains_key(layer) {
+            return Err(anyhow::anyhow!("No layer with name '{}'", layer));
+        }
+        let image = ws
+            .images
+            .get_mut(name)
+            .ok_or_else(|| anyhow::anyhow!("No image with name '{}'", name))?;
+        image.layer = layer.to_string();
+
+        ws.rebind_image(name)?;
+        ws.update_visibility(name);
+        ws.sort_images();
+        Ok(())
+    }
+
+    pub fn set_layer_visible(
+        &mut self,
+        window_id: u32,
+        name: &str,
+        visible: bool,
+    ) -> anyhow::Result<()> {
+        let ws = self
+            .windows
+            .get_mut(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+        let layer = ws
+            .layers
+            .get_mut(name)
+            .ok_or_else(|| anyhow::anyhow!("No layer with name '{}'", name))?;
+        layer.visible = visible;
+
+        let names: Vec<String> = ws
+            .images
+            .iter()
+            .filter(|(_, image)| image.layer == name)
+            .map(|(name, _)| name.clone())
+            .collect();
+        for image_name in names {
+            ws.update_visibility(&image_name);
+        }
+        Ok(())
+    }
+
+    pub fn set_image_visible(
+        &mut self,
+        window_id: u32,
+        name: &str,
+        visible: bool,
+    ) -> anyhow::Result<()> {
+        let ws = self
+            .windows
+            .get_mut(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+        let image = ws
+            .images
+            .get_mut(name)
+            .ok_or_else(|| anyhow::anyhow!("No image with name '{}'", name))?;
+        image.visible = visible;
+        ws.update_visibility(name);
+        Ok(())
+    }
+
+    pub fn set_image_z_order(
+        &mut self,
+        window_id: u32,
+        name: &str,
+        z_order: i32,
+    ) -> anyhow::Result<()> {
+        let ws = self
+            .windows
+            .get_mut(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+        let image = ws
+            .images
+            .get_mut(name)
+            .ok_or_else(|| anyhow::anyhow!("No image with name '{}'", name))?;
+        image.z_order = z_order;
+
+        // w warstwie `ignore_depth` z-order wybiera przebieg overlay
+        ws.rebind_image(name)?;
+        ws.sort_images();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // klucz przebiegu, na który trafia obrazek warstwy `ignore_depth`
+    fn overlay(layer_order: i32, z_order: i32) -> OverlayKey {
+        let ImageStage::Overlay(key) = stage_for(BlendMode::Alpha, true, layer_order, z_order)
+        else {
+            panic!("image of an ignore_depth layer is not on an overlay pass");
+        };
+        key
+    }
+
+    #[test]
+    fn overlay_passes_follow_layer_order_beyond_ten_layers() {
+        // przebiegi są rysowane w kolejności kluczy mapy `WindowState::overlays`;
+        // kolejność tworzenia warstw nie ma znaczenia – liczy się `order`
+        let orders = [7, -3, 12, 0, 5, 11, 1, -10, 9, 3, 15, 2, 8];
+        let mut passes = std::collections::BTreeMap::new();
+        for order in orders {
+            passes.insert(overlay(order, 0), order);
+        }
+        let drawn: Vec<i32> = passes.values().copied().collect();
+        let mut expected = orders.to_vec();
+        expected.sort();
+        assert_eq!(drawn, expected);
+    }
+
+    #[test]
+    fn z_order_orders_images_within_a_layer_only() {
+        assert!(overlay(1, 100) < overlay(2, -100));
+        assert!(overlay(1, -5) < overlay(1, 0));
+        assert!(overlay(1, 0) < overlay(1, 30));
+    }
+
+    #[test]
+    fn blend_mode_and_depth_choose_the_stage() {
+        assert_eq!(stage_for(BlendMode::Alpha, false, 4, 2), ImageStage::Main);
+        assert_eq!(
+            stage_for(BlendMode::Additive, true, 4, 2),
+            ImageStage::Blend(BlendMode::Additive)
+        );
+    }
+}
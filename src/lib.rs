@@ -5,7 +5,6 @@ use renderling::{
     camera::Camera,
     pbr::Material, // ⬅️ nowy
     stage::{Renderlet, Stage, Vertex},
-    Context,
 };
 
 use wasm_bindgen::prelude::*;
@@ -16,10 +15,56 @@ use winit::{
 
 use renderling::prelude::SlabAllocator;
 
+mod blend;
+mod camera;
+#[cfg(target_arch = "wasm32")]
+mod canvas;
+mod command;
+mod environment;
+mod events;
+mod gpu;
+#[cfg(target_arch = "wasm32")]
+mod js_api;
+mod layer;
+mod light;
+mod material;
+mod model;
+mod pointer;
+mod protocol;
+mod record;
+#[cfg(target_arch = "wasm32")]
+mod remote;
 mod req_animation_frame;
+mod scene;
+mod scene_io;
+#[cfg(not(target_arch = "wasm32"))]
+mod server;
+mod timeline;
+mod window;
+
+pub use blend::{BlendCompositor, BlendLayer, BlendMode, OpacityFade};
+pub use command::Command;
+pub use environment::Environment;
+#[cfg(not(target_arch = "wasm32"))]
+pub use events::subscribe_channel;
+pub use events::{
+    subscribe, unsubscribe, OutboundEvent, PointerButton, Subscriber, SubscriptionId,
+};
+pub use layer::{ImageStage, OverlayKey, OverlayPass, RenderLayer, DEFAULT_LAYER};
+pub use light::{LightDescriptor, LightHandle, LightObject, ShadowSettings};
+pub use material::{MaterialDescriptor, NamedMaterial};
+pub use model::{AnimationPlayback, ModelObject};
+pub use protocol::{Reply, Request, Responder};
+pub use record::{RecordEntry, Recorder, Replay};
+pub use scene_io::{
+    AnimationDescription, CameraDescription, EnvironmentDescription, ImageDescription,
+    LightDescription, ModelDescription, SceneDescription,
+};
+pub use timeline::{play_timeline, TimedCommand, Timeline};
+pub use window::{ClosePolicy, FullscreenMode, RedrawPolicy};
 
+// domyślny canvas okna 0, gdy strona nie poda innego (`?canvas=`)
 const WASM_CANVAS_ID: &str = "app-canvas";
-const WASM_CREATE_WINDOW: bool = true;
 const SIZE_OF_WORLD: f32 = 0.01;
 
 #[cfg(target_arch = "wasm32")]
@@ -53,6 +98,32 @@ pub fn get_global_proxy() -> Option<winit::event_loop::EventLoopProxy<CustomUser
     }
 }
 
+// Przy odtwarzaniu nagrania czas klatek pochodzi z nagrania, a nie z zegara
+static FRAME_CLOCK: std::sync::Mutex<Option<f64>> = std::sync::Mutex::new(None);
+
+pub fn set_frame_clock(time: Option<f64>) {
+    *FRAME_CLOCK.lock().unwrap() = time;
+}
+
+// Czas w sekundach od startu – std::time::Instant nie działa na wasm32
+pub fn now_seconds() -> f64 {
+    if let Some(time) = *FRAME_CLOCK.lock().unwrap() {
+        return time;
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        js_sys::Date::now() / 1000.0
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        static START: once_cell::sync::Lazy<std::time::Instant> =
+            once_cell::sync::Lazy::new(std::time::Instant::now);
+        START.elapsed().as_secs_f64()
+    }
+}
+
 pub static IMAGES_MAP: once_cell::sync::Lazy<
     std::sync::RwLock<std::collections::HashMap<String, ImageData>>,
 > = once_cell::sync::Lazy::new(|| std::sync::RwLock::new(std::collections::HashMap::new()));
@@ -75,18 +146,52 @@ pub fn remove_images_map(key: &str) -> Option<ImageData> {
 pub struct State {
     windows: std::collections::HashMap<u32, WindowState>,
     window_id_map: std::collections::HashMap<winit::window::WindowId, u32>,
+    gpu: gpu::SharedGpu,
+    // zdekodowane pliki tekstur: obraz z dysku czytamy raz, ale każde okno ładuje go
+    // do własnego atlasu, więc pamięć GPU na tekstury nie jest współdzielona
+    decoded_images: std::collections::HashMap<String, AtlasImage>,
 }
 pub struct WindowState {
     pub window: std::sync::Arc<winit::window::Window>,
     pub position: [i32; 2],
+    // piksele fizyczne na logiczny (devicePixelRatio w przeglądarce)
+    pub scale_factor: f64,
 
     ctx: renderling::Context,
     stage: Stage,
+    background_color: [f32; 4],
     tex: std::collections::HashMap<String, Hybrid<AtlasTexture>>,
     camera: Camera,
+    // `camera` w slabie głównego stage (przebiegi overlay i mieszania mają własne)
+    main_camera: Hybrid<Camera>,
     yaw: f32,
     pitch: f32,
     images: std::collections::HashMap<String, ImageObject>,
+    models: std::collections::HashMap<String, ModelObject>,
+    last_frame: Option<f64>,
+    lighting: bool,
+    lights: std::collections::HashMap<String, LightObject>,
+    environment: Option<Environment>,
+    materials: std::collections::HashMap<String, NamedMaterial>,
+    // przebiegi rysowane na wierzchu, bez testu głębi względem głównej sceny, rosnąco wg klucza
+    overlays: std::collections::BTreeMap<OverlayKey, OverlayPass>,
+    layers: std::collections::HashMap<String, RenderLayer>,
+    draw_order: Vec<String>,
+    blend_layers: std::collections::HashMap<BlendMode, BlendLayer>,
+    compositor: Option<BlendCompositor>,
+    // dziecko -> rodzic, dla obrazków i modeli
+    parents: std::collections::HashMap<String, String>,
+    close_policy: ClosePolicy,
+    redraw_policy: RedrawPolicy,
+    // scena zmieniła się od ostatniej klatki
+    needs_redraw: bool,
+    // ostatnia pozycja kursora w pikselach fizycznych
+    cursor: Option<glam::Vec2>,
+    hovered: Option<String>,
+    // obrazek, nad którym wciśnięto przycisk
+    pressed: std::collections::HashMap<PointerButton, String>,
+    // ścieżka tekstury -> alpha; None = nie dało się wczytać
+    hit_masks: std::collections::HashMap<String, Option<pointer::AlphaMask>>,
 }
 
 pub struct ImageObject {
@@ -94,7 +199,157 @@ pub struct ImageObject {
     pub vertices_cpu: Vec<Vertex>,
     pub renderlet: Hybrid<Renderlet>,
     pub material: Hybrid<Material>,
-    pub origin: glam::Vec3, //glam::Vec3,
+    // transformacja lokalna (względem rodzica), z niej liczymy vertices_cpu
+    pub position: glam::Vec3,
+    pub size: glam::Vec2,
+    pub rotation: f32,
+    pub cast_shadows: bool,
+    pub material_name: Option<String>, // None = własny materiał obrazka
+    pub double_sided: bool,
+    pub texture_path: String,
+    pub layer: String,
+    pub z_order: i32,
+    pub stage: ImageStage,
+    // kolor z przypisanego materiału nazwanego; tint i opacity go mnożą
+    pub base_color: [f32; 4],
+    pub tint: [f32; 4],
+    pub opacity: f32,
+    pub fade: Option<OpacityFade>,
+    pub blend: BlendMode,
+    pub visible: bool,
+    // próg alpha dla testu trafienia kursorem; 0 = cały prostokąt
+    pub hit_alpha: f32,
+}
+
+impl ImageObject {
+    // Wysyła vertices_cpu na GPU i podpina je pod renderlet
+    fn upload_vertices(&mut self, stage: &Stage) {
+        let mut vertices = self.vertices_cpu.clone();
+        if self.double_sided {
+            // tylna ściana: te same trójkąty z odwróconą kolejnością wierzchołków
+            for tri in self.vertices_cpu.chunks_exact(3) {
+                vertices.extend([tri[0].clone(), tri[2].clone(), tri[1].clone()]);
+            }
+        }
+        let new_hybrid = stage.new_vertices(vertices);
+        let mut renderlet = self.renderlet.get();
+        renderlet.vertices_array = new_hybrid.array();
+        self.renderlet.set(renderlet);
+        self.vertices = new_hybrid;
+    }
+}
+
+impl WindowState {
+    pub async fn new(
+        gpu: &gpu::SharedGpu,
+        window: std::sync::Arc<winit::window::Window>,
+        background_color: palette::Srgba,
+    ) -> anyhow::Result<Self> {
+        // ta sama pozycja co w `Moved` i `set_window_position` – zewnętrzna, z ramką
+        let initial_position = window
+            .outer_position()
+            .map(|pos| [pos.x, pos.y])
+            .unwrap_or([0, 0]);
+
+        let ctx = gpu.context_for(window.clone()).await?;
+        log::info!("created Context with size: {}", ctx.get_size());
+        // bufory stage'y w pikselach fizycznych – na HiDPI pełna rozdzielczość ekranu
+        let size = ctx.get_size();
+
+        let background_color = [
+            background_color.red,
+            background_color.green,
+            background_color.blue,
+            background_color.alpha,
+        ];
+        let stage = ctx
+            .new_stage()
+            .with_background_color(background_color)
+            .with_lighting(false)
+            .with_size(size);
+
+        let slab = SlabAllocator::new(&ctx, "test", wgpu::BufferUsages::empty());
+
+        let camera = Camera::default_perspective(1920.0, 1080.0);
+        let main_camera = stage.new_value(camera);
+
+        Ok(Self {
+            ctx,
+            stage,
+            background_color,
+            tex: std::collections::HashMap::new(),
+            camera,
+            main_camera,
+            yaw: 0.0,
+            pitch: 0.0,
+            images: std::collections::HashMap::new(),
+            models: std::collections::HashMap::new(),
+            last_frame: None,
+            lighting: false,
+            lights: std::collections::HashMap::new(),
+            environment: None,
+            materials: std::collections::HashMap::new(),
+            overlays: std::collections::BTreeMap::new(),
+            layers: std::collections::HashMap::from([(
+                DEFAULT_LAYER.to_string(),
+                RenderLayer::default(),
+            )]),
+            draw_order: Vec::new(),
+            blend_layers: std::collections::HashMap::new(),
+            compositor: None,
+            parents: std::collections::HashMap::new(),
+            close_policy: ClosePolicy::default(),
+            redraw_policy: RedrawPolicy::default(),
+            needs_redraw: true,
+            cursor: None,
+            hovered: None,
+            pressed: std::collections::HashMap::new(),
+            hit_masks: std::collections::HashMap::new(),
+            scale_factor: window.scale_factor(),
+            window,
+            position: initial_position, // Ustawiamy pozycję
+        })
+    }
+
+    // `size` w pikselach fizycznych (z `Resized`)
+    pub fn resize(&mut self, size: winit::dpi::PhysicalSize<u32>) {
+        // zminimalizowane okno – zostawiamy stare bufory
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+        let size = glam::UVec2::new(size.width, size.height);
+        self.ctx.set_size(size);
+        self.stage.set_size(size);
+        for pass in self.overlays.values() {
+            pass.stage.set_size(size);
+        }
+        for layer in self.blend_layers.values() {
+            layer.stage.set_size(size);
+        }
+        self.needs_redraw = true;
+    }
+
+    // Obrazki i modele dzielą przestrzeń nazw – komendy pozycji/obrotu/usuwania szukają w obu
+    pub fn ensure_name_free(&self, name: &str) -> anyhow::Result<()> {
+        if self.images.contains_key(name) {
+            return Err(anyhow::anyhow!("Image with name '{}' already exists", name));
+        }
+        if self.models.contains_key(name) {
+            return Err(anyhow::anyhow!("Model with name '{}' already exists", name));
+        }
+        Ok(())
+    }
+
+    fn upload_image(&mut self, name: &str) {
+        if let Some(image) = self.images.get_mut(name) {
+            let stage = match image.stage {
+                ImageStage::Main => &self.stage,
+                ImageStage::Overlay(key) => &self.overlays[&key].stage,
+                ImageStage::Blend(mode) => &self.blend_layers[&mode].stage,
+            };
+            image.upload_vertices(stage);
+        }
+    }
 }
 #[derive(Debug, Clone, Default)]
 pub struct ImageData {
@@ -106,6 +361,20 @@ pub struct ImageData {
     pub rotation: f32,
 }
 
+// Obraz z cache albo zdekodowany z dysku i dopisany do cache
+pub fn decoded_image(
+    cache: &mut std::collections::HashMap<String, AtlasImage>,
+    path: &str,
+) -> anyhow::Result<AtlasImage> {
+    if let Some(image) = cache.get(path) {
+        return Ok(image.clone());
+    }
+    let image = AtlasImage::from_path(path)
+        .map_err(|e| anyhow::anyhow!("Failed to load texture '{}': {:?}", path, e))?;
+    cache.insert(path.to_string(), image.clone());
+    Ok(image)
+}
+
 impl State {
     pub async fn new(
         window: Option<std::sync::Arc<winit::window::Window>>,
@@ -113,6 +382,8 @@ impl State {
         let mut state = Self {
             windows: std::collections::HashMap::new(),
             window_id_map: std::collections::HashMap::new(),
+            gpu: gpu::SharedGpu::new(),
+            decoded_images: std::collections::HashMap::new(),
         };
 
         if let Some(win) = window {
@@ -128,7 +399,7 @@ impl State {
                 .await?;
         }
 
-        println!("zainicjonowano State!");
+        log::info!("zainicjonowano State!");
         Ok(state)
     }
 
@@ -138,49 +409,31 @@ impl State {
         window: std::sync::Arc<winit::window::Window>,
         background_color: palette::Srgba,
     ) -> anyhow::Result<()> {
-        let initial_position = window
-            .inner_position()
-            .map(|pos| [pos.x, pos.y])
-            .unwrap_or([0, 0]);
-
-        let ctx = Context::from_winit_window(None, window.clone()).await;
-        log::info!("created Context with size: {}", ctx.get_size());
-
-        let stage = ctx
-            .new_stage()
-            .with_background_color([0.1, 0.2, 0.3, 1.0])
-            .with_lighting(false)
-            .with_size(glam::UVec2 { x: 1920, y: 1080 });
-
-        let slab = SlabAllocator::new(&ctx, "test", wgpu::BufferUsages::empty());
-
-        let camera = Camera::default_perspective(1920.0, 1080.0);
-
-        self.window_id_map.insert(window.id(), id);
-        self.windows.insert(
-            id,
-            WindowState {
-                ctx,
-                stage,
-                tex: std::collections::HashMap::new(),
-                camera,
-                yaw: 0.0,
-                pitch: 0.0,
-                images: std::collections::HashMap::new(),
-                window,
-                position: initial_position, // Ustawiamy pozycję
-            },
-        );
-
+        self.ensure_window_id_free(id)?;
+        let ws = WindowState::new(&self.gpu, window, background_color).await?;
+        self.insert_window(id, ws);
         Ok(())
     }
 
-    pub async fn remove_window(&mut self, id: u32) -> anyhow::Result<()> {
-        if let Some(removed_state) = self.windows.remove(&id) {
-            self.window_id_map.retain(|_, &mut v| v != id);
+    pub fn ensure_window_id_free(&self, id: u32) -> anyhow::Result<()> {
+        if self.windows.contains_key(&id) {
+            return Err(anyhow::anyhow!("Window with id {} already exists", id));
         }
+        Ok(())
+    }
 
-        self.windows.remove(&id);
+    pub fn insert_window(&mut self, id: u32, ws: WindowState) {
+        self.window_id_map.insert(ws.window.id(), id);
+        self.windows.insert(id, ws);
+        events::emit(OutboundEvent::WindowOpened { window: id });
+    }
+
+    pub fn remove_window(&mut self, id: u32) -> anyhow::Result<()> {
+        self.windows
+            .remove(&id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", id))?;
+        self.window_id_map.retain(|_, &mut v| v != id);
+        events::emit(OutboundEvent::WindowClosed { window: id });
         Ok(())
     }
 
@@ -188,6 +441,26 @@ impl State {
         &mut self,
         id: u32,
         texture_path: &str,
+    ) -> anyhow::Result<Hybrid<AtlasTexture>> {
+        let result = self.upload_texture(id, texture_path);
+        events::emit(match &result {
+            std::result::Result::Ok(_) => OutboundEvent::TextureLoaded {
+                window: id,
+                path: texture_path.to_string(),
+            },
+            Err(e) => OutboundEvent::TextureFailed {
+                window: id,
+                path: texture_path.to_string(),
+                error: e.to_string(),
+            },
+        });
+        result
+    }
+
+    fn upload_texture(
+        &mut self,
+        id: u32,
+        texture_path: &str,
     ) -> anyhow::Result<Hybrid<AtlasTexture>> {
         let ws = self
             .windows
@@ -195,8 +468,7 @@ impl State {
             .ok_or_else(|| anyhow::anyhow!("No window with id {}", id))?;
         let stage = &ws.stage;
 
-        let atlas_image = AtlasImage::from_path(texture_path)
-            .map_err(|e| anyhow::anyhow!("Failed to load texture '{}': {:?}", texture_path, e))?;
+        let atlas_image = decoded_image(&mut self.decoded_images, texture_path)?;
 
         let entries = stage.add_images([atlas_image])?;
         let texture = entries[0].clone();
@@ -214,6 +486,11 @@ impl State {
         y: f32,
         z: f32,
     ) -> anyhow::Result<()> {
+        self.windows
+            .get(&id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", id))?
+            .ensure_name_free(&name)?;
+
         // 1. sprawdzamy, czy tekstura już jest
         let texture_exists = self
             .windows
@@ -269,12 +546,6 @@ impl State {
             }
         }
 
-        let mut origin = glam::Vec3::ZERO;
-        for v in &vertices_cpu {
-            origin += v.position;
-        }
-        origin /= vertices_cpu.len() as f32;
-
         // 5. materiał
         let mut mat = Material::default();
         mat.albedo_texture_id = texture.id();
@@ -283,6 +554,7 @@ impl State {
         let renderlet = ws.stage.new_renderlet(Renderlet {
             vertices_array: vertices.array(),
             material_id: mat.id(),
+            camera_id: ws.main_camera.id(),
             ..Default::default()
         });
 
@@ -295,9 +567,26 @@ impl State {
                 renderlet,
                 material: mat,
                 vertices_cpu,
-                origin,
+                position: glam::Vec3::new(x, y, z),
+                size: glam::Vec2::new(width, height),
+                rotation: 0.0,
+                cast_shadows: false,
+                material_name: None,
+                double_sided: false,
+                texture_path,
+                layer: DEFAULT_LAYER.to_string(),
+                z_order: 0,
+                stage: ImageStage::Main,
+                base_color: [1.0, 1.0, 1.0, 1.0],
+                tint: [1.0, 1.0, 1.0, 1.0],
+                opacity: 1.0,
+                fade: None,
+                blend: BlendMode::Alpha,
+                visible: true,
+                hit_alpha: 0.0,
             },
         );
+        ws.sort_images();
 
         insert_images_map(
             name,
@@ -331,26 +620,12 @@ impl State {
             .get_mut(name)
             .ok_or_else(|| anyhow::anyhow!("No image with name '{}'", name))?;
 
-        // Wyciągamy pozycję pierwszego wierzchołka jako punkt odniesienia
-        let origin = image.vertices_cpu[0].position;
-
-        // Ustawiamy nową pozycję wszystkich wierzchołków
-        for vertex in &mut image.vertices_cpu {
-            vertex.position[0] = x + (vertex.position[0] - origin[0]);
-            vertex.position[1] = y + (vertex.position[1] - origin[1]);
-            vertex.position[2] = z + (vertex.position[2] - origin[2]);
-        }
-
-        // Tworzymy nowy HybridArray w stage
-        let new_hybrid = ws.stage.new_vertices(image.vertices_cpu.clone());
-
-        // Aktualizujemy renderlet
-        let mut renderlet = image.renderlet.get();
-        renderlet.vertices_array = new_hybrid.array();
-        image.renderlet.set(renderlet);
+        // pozycja lewego-dolnego rogu (vertex 0), względem rodzica
+        image.position = glam::Vec3::new(x, y, z);
 
-        // Zmieniamy HybridArray w ImageObject
-        image.vertices = new_hybrid;
+        // Nowe wierzchołki obrazka i jego dzieci
+        ws.update_object_transform(name);
+        ws.sort_images();
 
         insert_images_map(
             name,
@@ -381,30 +656,14 @@ impl State {
             .get_mut(name)
             .ok_or_else(|| anyhow::anyhow!("No image with name '{}'", name))?;
 
-        // Lewy–górny róg (vertex 0) – traktujemy jako punkt odniesienia
-        let p = image.vertices_cpu[0].position;
-        let x0 = p.x;
-        let y0 = p.y;
-        let z0 = p.z;
-
-        // Przeliczenie pikseli na jednostki świata
-        let w = width_px as f32 * SIZE_OF_WORLD;
-        let h = height_px as f32 * SIZE_OF_WORLD;
-
-        // Ustawiamy pozycje 6 wierzchołków (2 trójkąty)
-        image.vertices_cpu[0].position = [x0, y0, z0].into(); // (0,0)
-        image.vertices_cpu[1].position = [x0 + w, y0, z0].into(); // (w,0)
-        image.vertices_cpu[2].position = [x0, y0 + h, z0].into(); // (0,h)
-        image.vertices_cpu[3].position = [x0 + w, y0, z0].into(); // (w,0)
-        image.vertices_cpu[4].position = [x0 + w, y0 + h, z0].into(); // (w,h)
-        image.vertices_cpu[5].position = [x0, y0 + h, z0].into(); // (0,h)
-
-        // Aktualizacja na GPU
-        let new_hybrid = ws.stage.new_vertices(image.vertices_cpu.clone());
-        let mut renderlet = image.renderlet.get();
-        renderlet.vertices_array = new_hybrid.array();
-        image.renderlet.set(renderlet);
-        image.vertices = new_hybrid;
+        // Przeliczenie pikseli na jednostki świata, róg (vertex 0) zostaje w miejscu
+        image.size = glam::Vec2::new(
+            width_px as f32 * SIZE_OF_WORLD,
+            height_px as f32 * SIZE_OF_WORLD,
+        );
+
+        // Aktualizacja na GPU – środek obrotu się przesunął, więc dzieci też
+        ws.update_object_transform(name);
 
         insert_images_map(
             name,
@@ -435,21 +694,10 @@ impl State {
             .get_mut(name)
             .ok_or_else(|| anyhow::anyhow!("No image with name '{}'", name))?;
 
-        let origin = image.origin; // używamy zaktualizowanego origin
-
-        let rot = glam::Mat4::from_rotation_z(angle_rad);
+        // kąt bezwzględny, obrót wokół środka obrazka
+        image.rotation = angle_rad;
 
-        for vertex in &mut image.vertices_cpu {
-            let pos = vertex.position - origin;
-            let rotated = rot.transform_point3(pos);
-            vertex.position = rotated + origin;
-        }
-
-        let new_hybrid = ws.stage.new_vertices(image.vertices_cpu.clone());
-        let mut renderlet = image.renderlet.get();
-        renderlet.vertices_array = new_hybrid.array();
-        image.renderlet.set(renderlet);
-        image.vertices = new_hybrid;
+        ws.update_object_transform(name);
 
         insert_images_map(
             name,
@@ -471,6 +719,9 @@ impl State {
         if let Some(image) = ws.images.remove(name) {
             // Możesz tu też ewentualnie wyczyścić zasoby GPU, jeśli renderling tego wymaga
             // np. image.renderlet.dispose() lub podobne, jeśli API renderling wspiera.
+            ws.stage_of(image.stage).remove_renderlet(&image.renderlet);
+            ws.draw_order.retain(|n| n != name);
+            ws.unlink_object(name);
             remove_images_map(name);
         } else {
             return Err(anyhow::anyhow!("No image with name '{}'", name));
@@ -502,18 +753,19 @@ impl State {
         // Iterujemy przez istniejące tekstury w `ws.tex`
         for (path, handle) in ws.tex.iter() {
             if path != texture_path {
-                // Dla każdej tekstury, którą chcemy zachować, musimy ponownie ją załadować
-                // jako AtlasImage, aby przekazać do set_images.
-                // WAŻNE: To jest mniej wydajne, ponieważ ponownie ładujemy dane obrazu z dysku.
-                // Idealnie, AtlasImage powinno być odtworzone z już załadowanych danych.
-                // Jednak renderling::Stage::set_images przyjmuje AtlasImage.
-                let atlas_image = AtlasImage::from_path(path).map_err(|e| {
-                    anyhow::anyhow!(
-                        "Failed to re-load texture '{}' for set_images: {:?}",
-                        path,
-                        e
-                    )
-                })?;
+                // Dla każdej tekstury, którą chcemy zachować, potrzebujemy AtlasImage
+                // do set_images – bierzemy zdekodowany obraz z cache, a z dysku
+                // czytamy tylko wtedy, gdy go tam nie ma.
+                let atlas_image = match self.decoded_images.get(path) {
+                    Some(image) => image.clone(),
+                    None => blend::load_atlas_image(path).map_err(|e| {
+                        anyhow::anyhow!(
+                            "Failed to re-load texture '{}' for set_images: {:?}",
+                            path,
+                            e
+                        )
+                    })?,
+                };
                 images_to_keep.push(atlas_image);
             } else {
                 removed_handle_id = Some(handle.id());
@@ -537,6 +789,15 @@ impl State {
         //    To spowoduje usunięcie z VRAM wszystkich tekstur, których nie ma w `images_to_keep`.
         ws.stage.set_images(images_to_keep.into_iter())?;
 
+        // obraz zostaje w cache, dopóki używa go jakiekolwiek okno
+        if !self
+            .windows
+            .values()
+            .any(|ws| ws.tex.contains_key(texture_path))
+        {
+            self.decoded_images.remove(texture_path);
+        }
+
         log::info!(
             "Texture '{}' deleted from window {} using set_images method.",
             texture_path,
@@ -548,22 +809,89 @@ impl State {
 
 pub enum CustomUserEvent {
     StateInitialized(State),
+    // Okno, którego kontekst GPU powstał asynchronicznie (canvas w przeglądarce)
+    WindowReady(u32, anyhow::Result<WindowState>),
     CreateWindow(u32, u32, u32, String, palette::Srgba, bool), // ID | width | height | Name | BackGroundColor | Visible
     DeleteWindow(u32),                                         // ID
-    LoadTexture(u32, String),                                  // WindowId | TexturePath
+    AttachCanvas(u32, String, palette::Srgba), // ID | Canvas element id | BackGroundColor (wasm)
+    LoadTexture(u32, String),                  // WindowId | TexturePath
     AddImage(u32, String, String, f32, f32, f32), // WindowId | Name | TexturePath | X | Y | Z
 
     SetImagePosition(u32, String, f32, f32, f32), // WindowId | Name | dx | dy | dz
     SetImageSize(u32, String, u32, u32),          // WindowId | Name | scale_x | scale_y
 
-    // TODO: naprawić SetImageRotation i SetImageOrigin ponieważ Origin jest tam gdzie 0,0 globalne. a więc po przesunięciu obrazka się psuje
+    // TODO: SetImageOrigin – na razie obrót jest zawsze wokół środka obrazka
     SetImageRotation(u32, String, f32), // WindowId | Name | angle_rad (obrót wokół Z)
     // SetImageOrigin(u32, String, f32, f32),                     // WindowId | Name | x | y
-    DeleteImage(u32, String), // WindowId | Name
+    DeleteImage(u32, String),   // WindowId | Name
     DeleteTexture(u32, String), // WindowId | TexturePath
 
-                              // TODO - inne:
-                              // sprawdzić czy to przez proxy taka kamera była
+    // TODO - inne:
+    // sprawdzić czy to przez proxy taka kamera była
+    LoadModel(u32, String, String, f32, f32, f32), // WindowId | Name | ModelPath | X | Y | Z
+    SetModelPosition(u32, String, f32, f32, f32),  // WindowId | Name | X | Y | Z
+    SetModelRotation(u32, String, f32, f32, f32),  // WindowId | Name | rot_x | rot_y | rot_z (rad)
+    SetModelScale(u32, String, f32, f32, f32),     // WindowId | Name | scale_x | scale_y | scale_z
+    DeleteModel(u32, String),                      // WindowId | Name
+
+    ListAnimations(u32, String),                   // WindowId | Model
+    PlayAnimation(u32, String, String, f32, bool), // WindowId | Model | AnimName | speed | looping
+    PauseAnimation(u32, String, String),           // WindowId | Model | AnimName
+    StopAnimation(u32, String, String),            // WindowId | Model | AnimName
+    SeekAnimation(u32, String, String, f32),       // WindowId | Model | AnimName | time_s
+    SetAnimationWeight(u32, String, String, f32), // WindowId | Model | AnimName | weight (0..1, blend)
+
+    SetLighting(u32, bool),                     // WindowId | enabled
+    AddLight(u32, String, LightDescriptor),     // WindowId | Name | Light
+    SetLight(u32, String, LightDescriptor),     // WindowId | Name | Light
+    DeleteLight(u32, String),                   // WindowId | Name
+    EnableShadows(u32, String, ShadowSettings), // WindowId | LightName | settings
+    DisableShadows(u32, String),                // WindowId | LightName
+    SetCastShadows(u32, String, bool),          // WindowId | ObjectName | cast
+
+    SetEnvironment(u32, String), // WindowId | HdrPath (equirectangular)
+    SetEnvironmentExposure(u32, f32), // WindowId | exposure
+    SetEnvironmentRotation(u32, f32), // WindowId | angle_rad (obrót wokół Y)
+
+    CreateMaterial(u32, String, MaterialDescriptor), // WindowId | Name | Material
+    SetMaterial(u32, String, MaterialDescriptor),    // WindowId | Name | Material
+    DeleteMaterial(u32, String),                     // WindowId | Name
+    AssignMaterial(u32, String, String),             // WindowId | ObjectName | MaterialName
+    SetImageTint(u32, String, [f32; 4]),             // WindowId | Name | rgba
+
+    SetLayer(u32, String, i32, bool), // WindowId | LayerName | order | ignore_depth
+    DeleteLayer(u32, String),         // WindowId | LayerName
+    SetImageLayer(u32, String, String), // WindowId | Name | LayerName
+    SetImageZOrder(u32, String, i32), // WindowId | Name | z_order
+    SetImageBlendMode(u32, String, BlendMode), // WindowId | Name | mode
+    SetImageOpacity(u32, String, f32, f32), // WindowId | Name | opacity (0..1) | duration_s
+    SetImageVisible(u32, String, bool), // WindowId | Name | visible
+    SetLayerVisible(u32, String, bool), // WindowId | LayerName | visible
+    SetImageHitAlpha(u32, String, f32), // WindowId | Name | alpha threshold (0 = whole quad)
+
+    SetParent(u32, String, String),  // WindowId | Child | Parent
+    DetachFromParent(u32, String),   // WindowId | Child
+    DeleteObject(u32, String, bool), // WindowId | Name | recursive (razem z dziećmi)
+
+    SaveScene(u32, String), // WindowId | Path (.ron / .json)
+    LoadScene(u32, String), // WindowId | Path (.ron / .json)
+
+    SetClosePolicy(u32, ClosePolicy),   // WindowId | policy
+    SetWindowPosition(u32, i32, i32),   // WindowId | x | y (physical px)
+    SetWindowSize(u32, u32, u32),       // WindowId | width | height (logical px)
+    SetWindowTitle(u32, String),        // WindowId | title
+    SetFullscreen(u32, FullscreenMode), // WindowId | mode
+    SetDecorations(u32, bool),          // WindowId | decorations
+    SetAlwaysOnTop(u32, bool),          // WindowId | always_on_top
+    SetVisible(u32, bool),              // WindowId | visible
+    SetCursorVisible(u32, bool),        // WindowId | visible
+    SetCursorIcon(u32, String),         // WindowId | CSS cursor name
+    Minimize(u32, bool),                // WindowId | minimized
+    Maximize(u32, bool),                // WindowId | maximized
+    SetRedrawPolicy(u32, RedrawPolicy), // WindowId | policy
+
+    // Komenda z protokołu (stdin); `Responder` dostaje odpowiedź po wykonaniu
+    Command(Command, Option<Responder>),
 }
 
 impl App {
@@ -571,6 +899,15 @@ impl App {
         Self {
             proxy: event_loop.create_proxy(),
             state: std::sync::Arc::new(std::sync::RwLock::new(None)),
+            recorder: None,
+            replay: None,
+            frame: 0,
+            hidden_windows: false,
+            pending_windows: std::collections::HashMap::new(),
+            #[cfg(target_arch = "wasm32")]
+            canvas: None,
+            #[cfg(target_arch = "wasm32")]
+            created_canvases: std::collections::HashSet::new(),
         }
     }
 }
@@ -578,6 +915,20 @@ impl App {
 pub struct App {
     pub proxy: winit::event_loop::EventLoopProxy<CustomUserEvent>,
     pub state: std::sync::Arc<std::sync::RwLock<Option<State>>>,
+    pub recorder: Option<Recorder>,
+    pub replay: Option<Replay>,
+    // liczba wyrenderowanych klatek (wszystkich okien)
+    pub frame: u64,
+    // okna tworzone bez pokazywania, np. przy odtwarzaniu nagrania
+    pub hidden_windows: bool,
+    // odpowiedzi na komendy tworzące okno, wysyłane dopiero po WindowReady
+    pub pending_windows: std::collections::HashMap<u32, Responder>,
+    // canvas okna 0; None = winit tworzy własny, niepodpięty do strony
+    #[cfg(target_arch = "wasm32")]
+    pub canvas: Option<String>,
+    // okna, których canvas utworzyliśmy sami i usuwamy razem z oknem
+    #[cfg(target_arch = "wasm32")]
+    pub created_canvases: std::collections::HashSet<u32>,
 }
 
 impl ApplicationHandler<CustomUserEvent> for App {
@@ -586,6 +937,8 @@ impl ApplicationHandler<CustomUserEvent> for App {
         {
             let state: State = pollster::block_on(State::new(None)).expect("state init");
             self.state = std::sync::Arc::new(std::sync::RwLock::new(Some(state)));
+            // klient może wysyłać komendy już przed tym, ale dopiero teraz mają na czym działać
+            events::emit(OutboundEvent::Ready);
         }
         #[cfg(target_arch = "wasm32")]
         {
@@ -593,16 +946,11 @@ impl ApplicationHandler<CustomUserEvent> for App {
 
             let mut window_attributes = winit::window::Window::default_attributes();
 
-            if WASM_CREATE_WINDOW == true {
-                use wasm_bindgen::JsCast;
+            if let Some(canvas_id) = &self.canvas {
                 use winit::platform::web::WindowAttributesExtWebSys;
 
-                let window = web_sys::window().unwrap();
-                let document = window.document().unwrap();
-                let canvas = document.get_element_by_id(WASM_CANVAS_ID).unwrap();
-                let html_canvas_element: web_sys::HtmlCanvasElement = canvas.unchecked_into();
-
-                window_attributes = window_attributes.with_canvas(Some(html_canvas_element))
+                let canvas = canvas::find_canvas(canvas_id).expect("canvas for window 0");
+                window_attributes = window_attributes.with_canvas(Some(canvas))
             }
 
             let window = Arc::new(
@@ -629,133 +977,29 @@ impl ApplicationHandler<CustomUserEvent> for App {
         event_loop: &winit::event_loop::ActiveEventLoop,
         event: CustomUserEvent,
     ) {
-        match event {
-            CustomUserEvent::StateInitialized(state) => {
-                *self.state.write().unwrap() = Some(state);
-            }
-            CustomUserEvent::CreateWindow(id, width, height, title, background_color, visible) => {
-                #[cfg(not(target_arch = "wasm32"))]
-                {
-                    let window_attributes = winit::window::Window::default_attributes()
-                        .with_title(title)
-                        .with_inner_size(winit::dpi::PhysicalSize::new(width, height))
-                        .with_visible(visible)
-                        .with_transparent(true);
-
-                    let new_window = event_loop.create_window(window_attributes).unwrap();
-                    let new_window = std::sync::Arc::new(new_window);
-
-                    let state_arc_clone = std::sync::Arc::clone(&self.state);
-                    let mut state_guard = state_arc_clone.write().unwrap();
-                    if let Some(state_inner) = state_guard.as_mut() {
-                        let _ = pollster::block_on(state_inner.add_window(
-                            id,
-                            new_window,
-                            background_color,
-                        ));
-                    }
-                }
-                #[cfg(target_arch = "wasm32")]
-                log::warn!("Unable to Create window in arch wasm32");
-            }
-            CustomUserEvent::DeleteWindow(id) => {
-                #[cfg(not(target_arch = "wasm32"))]
-                {
-                    let state_arc_clone = std::sync::Arc::clone(&self.state);
-                    let mut state_guard = state_arc_clone.write().unwrap();
-                    if let Some(state_inner) = state_guard.as_mut() {
-                        let _ = pollster::block_on(state_inner.remove_window(id));
-                    }
-                }
-                #[cfg(target_arch = "wasm32")]
-                log::warn!("Unable to Delete window in arch wasm32");
-            }
-            CustomUserEvent::LoadTexture(window_id, texture_path) => {
-                #[cfg(not(target_arch = "wasm32"))]
-                if let Some(state) = self.state.write().unwrap().as_mut() {
-                    let _ =
-                        pollster::block_on(async { state.load_texture(window_id, &texture_path) });
-                }
-                #[cfg(target_arch = "wasm32")]
-                if let Some(state) = self.state.write().unwrap().as_mut() {
-                    let _ = state.load_texture(window_id, &texture_path);
-                }
-            }
-            CustomUserEvent::AddImage(window_id, name, texture_path, x, y, z) => {
-                #[cfg(not(target_arch = "wasm32"))]
-                if let Some(state) = self.state.write().unwrap().as_mut() {
-                    let _ = pollster::block_on(async {
-                        state.add_image(window_id, name, texture_path, x, y, z)
-                    });
-                }
-                #[cfg(target_arch = "wasm32")]
-                if let Some(state) = self.state.write().unwrap().as_mut() {
-                    let _ = state.add_image(window_id, name, texture_path, x, y, z);
-                }
-            }
-
-            CustomUserEvent::SetImagePosition(window_id, name, dx, dy, dz) => {
-                #[cfg(not(target_arch = "wasm32"))]
-                if let Some(state) = self.state.write().unwrap().as_mut() {
-                    let _ = pollster::block_on(async {
-                        state.set_image_position(window_id, &name, dx, dy, dz)
-                    });
-                }
-                #[cfg(target_arch = "wasm32")]
-                if let Some(state) = self.state.write().unwrap().as_mut() {
-                    let _ = state.set_image_position(window_id, &name, dx, dy, dz);
-                }
-            }
-            CustomUserEvent::SetImageSize(window_id, name, scale_x, scale_y) => {
-                #[cfg(not(target_arch = "wasm32"))]
-                if let Some(state) = self.state.write().unwrap().as_mut() {
-                    let _ = pollster::block_on(async {
-                        state.set_image_size(window_id, &name, scale_x, scale_y)
-                    });
-                }
-                #[cfg(target_arch = "wasm32")]
-                if let Some(state) = self.state.write().unwrap().as_mut() {
-                    let _ = state.set_image_size(window_id, &name, scale_x, scale_y);
-                }
-            }
-            CustomUserEvent::SetImageRotation(window_id, name, angle) => {
-                if let Some(state) = self.state.write().unwrap().as_mut() {
-                    let _ = state.set_image_rotation(window_id, &name, angle);
-                }
-            }
-            // CustomUserEvent::SetImageOrigin(window_id, name, x, y) => {
-            //     if let Some(state) = self.state.write().unwrap().as_mut() {
-            //         let _ = state.set_image_origin(window_id, &name, x, y);
-            //     }
-            // }
-            // CustomUserEvent::DeleteImage(window_id, name) => {
-            //     if let Some(state) = self.state.write().unwrap().as_mut() {
-            //         let _ = state.delete_image(window_id, &name);
-            //     }
-            // },
-            CustomUserEvent::DeleteImage(window_id, name) => {
-                #[cfg(not(target_arch = "wasm32"))]
-                if let Some(state) = self.state.write().unwrap().as_mut() {
-                    let _ = pollster::block_on(async { state.delete_image(window_id, &name) });
-                }
-
-                #[cfg(target_arch = "wasm32")]
-                if let Some(state) = self.state.write().unwrap().as_mut() {
-                    let _ = state.delete_image(window_id, &name);
-                }
-            }
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.record_event(self.frame, now_seconds(), &event);
+        }
+        if let Err(e) = self.handle_event(event_loop, event) {
+            log::error!("{e}");
+        }
+    }
 
-            CustomUserEvent::DeleteTexture(window_id, texture_path) => {
-                if let Some(state) = self.state.write().unwrap().as_mut() {
-                    #[cfg(not(target_arch = "wasm32"))]
-                    let _ = pollster::block_on(async {
-                        state.delete_texture(window_id, &texture_path)
-                    });
-                    #[cfg(target_arch = "wasm32")]
-                    let _ = state.delete_texture(window_id, &texture_path);
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        if let Some(replay) = self.replay.as_mut() {
+            for (time, event) in replay.due_events(self.frame) {
+                // komenda widzi ten sam czas co w nagraniu, np. start przejścia opacity
+                set_frame_clock(Some(time));
+                if let Err(e) = self.handle_event(event_loop, event) {
+                    log::error!("{e}");
                 }
             }
         }
+        if self.replay_without_redraw() {
+            self.step_replay_without_redraw(event_loop);
+            return;
+        }
+        self.schedule_redraws(event_loop);
     }
 
     fn window_event(
@@ -764,6 +1008,7 @@ impl ApplicationHandler<CustomUserEvent> for App {
         window_id: WindowId,
         event: WindowEvent,
     ) {
+        let replay_without_redraw = self.replay_without_redraw();
         let state_lock = self.state.clone();
         let mut state_guard = state_lock.write().unwrap();
         let state = if let Some(state) = state_guard.as_mut() {
@@ -786,20 +1031,99 @@ impl ApplicationHandler<CustomUserEvent> for App {
             return;
         };
 
+        if let Some(outbound) = OutboundEvent::from_window_event(id, ws.scale_factor, &event) {
+            events::emit(outbound);
+        }
+
         match event {
-            WindowEvent::CloseRequested => event_loop.exit(),
-            WindowEvent::Resized(new_size) => {
-                ws.ctx
-                    .set_size(glam::UVec2::new(new_size.width, new_size.height));
-                ws.window.request_redraw();
+            WindowEvent::CloseRequested => {
+                let policy = ws.close_policy;
+                match policy {
+                    ClosePolicy::ExitApp => event_loop.exit(),
+                    ClosePolicy::CloseWindow => {
+                        if let Err(e) = state.remove_window(id) {
+                            log::error!("{e}");
+                        }
+                        if state.windows.is_empty() {
+                            event_loop.exit();
+                        }
+                    }
+                    ClosePolicy::Notify => {}
+                }
+            }
+            WindowEvent::Resized(new_size) => ws.resize(new_size),
+            WindowEvent::Moved(position) => ws.position = [position.x, position.y],
+            WindowEvent::CursorMoved { position, .. } => {
+                ws.pointer_moved(id, glam::Vec2::new(position.x as f32, position.y as f32));
+            }
+            WindowEvent::CursorLeft { .. } => ws.pointer_left(id),
+            WindowEvent::MouseInput { state, button, .. } => {
+                ws.pointer_button(id, button.into(), state.is_pressed());
+            }
+            // dotyk działa jak lewy przycisk myszy
+            WindowEvent::Touch(touch) => {
+                let position = glam::Vec2::new(touch.location.x as f32, touch.location.y as f32);
+                match touch.phase {
+                    winit::event::TouchPhase::Started => {
+                        ws.pointer_moved(id, position);
+                        ws.pointer_button(id, PointerButton::Left, true);
+                    }
+                    winit::event::TouchPhase::Moved => ws.pointer_moved(id, position),
+                    winit::event::TouchPhase::Ended => {
+                        ws.pointer_moved(id, position);
+                        ws.pointer_button(id, PointerButton::Left, false);
+                        ws.pointer_left(id);
+                    }
+                    winit::event::TouchPhase::Cancelled => ws.pointer_left(id),
+                }
+            }
+            // nowy rozmiar fizyczny przychodzi zaraz potem w `Resized`
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                log::info!("Window {} scale factor: {}", id, scale_factor);
+                ws.scale_factor = scale_factor;
+                ws.needs_redraw = true;
             }
             WindowEvent::RedrawRequested => {
                 use std::result::Result::Ok;
+
+                // klatki ukrytego odtwarzania przesuwa już `about_to_wait`, tu tylko rysujemy
+                let dt = if replay_without_redraw {
+                    0.0
+                } else {
+                    // po końcu nagrania zegar stoi na ostatniej klatce
+                    if let Some(time) = self.replay.as_ref().and_then(|r| r.frame_time(self.frame))
+                    {
+                        set_frame_clock(Some(time));
+                    }
+                    let now = now_seconds();
+                    if let Some(recorder) = self.recorder.as_mut() {
+                        recorder.record_frame(self.frame, now);
+                    }
+                    self.frame += 1;
+                    ws.advance_frame(now)
+                };
+
                 if let Ok(frame) = ws.ctx.get_next_frame() {
-                    ws.stage.render(&frame.view());
+                    let view = frame.view();
+                    ws.stage.render(&view);
+                    ws.render_blend_layers(&view);
+                    for pass in ws.overlays.values() {
+                        pass.stage.render(&view);
+                    }
                     frame.present();
                 }
-                ws.window.request_redraw();
+                if !replay_without_redraw {
+                    events::emit(OutboundEvent::FrameTick {
+                        window: id,
+                        frame: self.frame,
+                        dt,
+                    });
+                }
+                ws.needs_redraw = false;
+                // po bezczynności następna klatka startuje z dt = 0, zamiast przeskakiwać animacje
+                if ws.next_redraw().is_none() {
+                    ws.last_frame = None;
+                }
             }
             WindowEvent::KeyboardInput {
                 event:
@@ -869,16 +1193,498 @@ impl ApplicationHandler<CustomUserEvent> for App {
                 // }
 
                 // cam.set(c);
-                ws.window.request_redraw();
+                ws.needs_redraw = true;
             }
             _ => {}
         }
     }
 }
 
+impl App {
+    // Wykonuje zdarzenie – z proxy albo z odtwarzanego nagrania.
+    // Zwraca ewentualny wynik komendy (np. listę animacji) dla protokołu stdin
+    fn handle_event(
+        &mut self,
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        event: CustomUserEvent,
+    ) -> anyhow::Result<Option<serde_json::Value>> {
+        // okno, którego scenę zmienia zdarzenie – przy OnDemand trzeba je przerysować
+        let target = Command::from_event(&event).map(|command| command.window());
+        match event {
+            CustomUserEvent::StateInitialized(state) => {
+                *self.state.write().unwrap() = Some(state);
+                events::emit(OutboundEvent::Ready);
+            }
+            CustomUserEvent::WindowReady(id, ws) => {
+                let result = ws.and_then(|ws| {
+                    self.with_state(|state| {
+                        state.insert_window(id, ws);
+                        Ok(())
+                    })
+                });
+                if let Some(responder) = self.pending_windows.remove(&id) {
+                    responder(match &result {
+                        std::result::Result::Ok(()) => Reply::from_result(&Ok(None)),
+                        Err(e) => Reply::error(None, e.to_string()),
+                    });
+                }
+                result?;
+            }
+            CustomUserEvent::CreateWindow(id, width, height, title, background_color, visible) => {
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    // przed utworzeniem okna systemowego, żeby nie zostało osierocone
+                    self.with_state(|state| state.ensure_window_id_free(id))?;
+                    let window_attributes = winit::window::Window::default_attributes()
+                        .with_title(title)
+                        .with_inner_size(winit::dpi::LogicalSize::new(width, height))
+                        .with_visible(visible && !self.hidden_windows)
+                        .with_transparent(true);
+
+                    let new_window =
+                        std::sync::Arc::new(event_loop.create_window(window_attributes)?);
+                    self.with_state(|state| {
+                        pollster::block_on(state.add_window(id, new_window, background_color))
+                    })?;
+                }
+                #[cfg(target_arch = "wasm32")]
+                {
+                    use winit::platform::web::WindowAttributesExtWebSys;
+
+                    let window_attributes = winit::window::Window::default_attributes()
+                        .with_title(title)
+                        .with_inner_size(winit::dpi::LogicalSize::new(width, height))
+                        .with_visible(visible && !self.hidden_windows)
+                        .with_append(true);
+                    self.spawn_canvas_window(event_loop, id, window_attributes, background_color)?;
+                    self.created_canvases.insert(id);
+                }
+            }
+            CustomUserEvent::DeleteWindow(id) => {
+                #[cfg(target_arch = "wasm32")]
+                if self.created_canvases.remove(&id) {
+                    use winit::platform::web::WindowExtWebSys;
+
+                    self.with_state(|state| {
+                        if let Some(canvas) =
+                            state.windows.get(&id).and_then(|ws| ws.window.canvas())
+                        {
+                            canvas.remove();
+                        }
+                        Ok(())
+                    })?;
+                }
+                self.with_state(|state| state.remove_window(id))?;
+            }
+            CustomUserEvent::AttachCanvas(id, canvas_id, background_color) => {
+                self.attach_canvas(event_loop, id, &canvas_id, background_color)?;
+            }
+            CustomUserEvent::LoadTexture(window_id, texture_path) => {
+                self.with_state(|state| state.load_texture(window_id, &texture_path))?;
+            }
+            CustomUserEvent::AddImage(window_id, name, texture_path, x, y, z) => {
+                self.with_state(|state| state.add_image(window_id, name, texture_path, x, y, z))?;
+            }
+
+            CustomUserEvent::SetImagePosition(window_id, name, dx, dy, dz) => {
+                self.with_state(|state| state.set_image_position(window_id, &name, dx, dy, dz))?;
+            }
+            CustomUserEvent::SetImageSize(window_id, name, scale_x, scale_y) => {
+                self.with_state(|state| state.set_image_size(window_id, &name, scale_x, scale_y))?;
+            }
+            CustomUserEvent::SetImageRotation(window_id, name, angle) => {
+                self.with_state(|state| state.set_image_rotation(window_id, &name, angle))?;
+            }
+            // CustomUserEvent::SetImageOrigin(window_id, name, x, y) => {
+            //     if let Some(state) = self.state.write().unwrap().as_mut() {
+            //         let _ = state.set_image_origin(window_id, &name, x, y);
+            //     }
+            // }
+            CustomUserEvent::DeleteImage(window_id, name) => {
+                self.with_state(|state| state.delete_image(window_id, &name))?;
+            }
+            CustomUserEvent::DeleteTexture(window_id, texture_path) => {
+                self.with_state(|state| state.delete_texture(window_id, &texture_path))?;
+            }
+
+            CustomUserEvent::LoadModel(window_id, name, model_path, x, y, z) => {
+                self.with_state(|state| state.load_model(window_id, name, model_path, x, y, z))?;
+            }
+            CustomUserEvent::SetModelPosition(window_id, name, x, y, z) => {
+                self.with_state(|state| state.set_model_position(window_id, &name, x, y, z))?;
+            }
+            CustomUserEvent::SetModelRotation(window_id, name, rot_x, rot_y, rot_z) => {
+                self.with_state(|state| {
+                    state.set_model_rotation(window_id, &name, rot_x, rot_y, rot_z)
+                })?;
+            }
+            CustomUserEvent::SetModelScale(window_id, name, sx, sy, sz) => {
+                self.with_state(|state| state.set_model_scale(window_id, &name, sx, sy, sz))?;
+            }
+            CustomUserEvent::DeleteModel(window_id, name) => {
+                self.with_state(|state| state.delete_model(window_id, &name))?;
+            }
+            CustomUserEvent::ListAnimations(window_id, model) => {
+                let names = self.with_state(|state| state.list_animations(window_id, &model))?;
+                log::info!("Animations of '{}': {:?}", model, names);
+                return Ok(Some(serde_json::json!(names)));
+            }
+            CustomUserEvent::PlayAnimation(window_id, model, anim_name, speed, looping) => {
+                self.with_state(|state| {
+                    state.play_animation(window_id, &model, &anim_name, speed, looping)
+                })?;
+            }
+            CustomUserEvent::PauseAnimation(window_id, model, anim_name) => {
+                self.with_state(|state| state.pause_animation(window_id, &model, &anim_name))?;
+            }
+            CustomUserEvent::StopAnimation(window_id, model, anim_name) => {
+                self.with_state(|state| state.stop_animation(window_id, &model, &anim_name))?;
+            }
+            CustomUserEvent::SeekAnimation(window_id, model, anim_name, time) => {
+                self.with_state(|state| state.seek_animation(window_id, &model, &anim_name, time))?;
+            }
+            CustomUserEvent::SetAnimationWeight(window_id, model, anim_name, weight) => {
+                self.with_state(|state| {
+                    state.set_animation_weight(window_id, &model, &anim_name, weight)
+                })?;
+            }
+
+            CustomUserEvent::SetLighting(window_id, enabled) => {
+                self.with_state(|state| state.set_lighting(window_id, enabled))?;
+            }
+            CustomUserEvent::AddLight(window_id, name, descriptor) => {
+                self.with_state(|state| state.add_light(window_id, name, descriptor))?;
+            }
+            CustomUserEvent::SetLight(window_id, name, descriptor) => {
+                self.with_state(|state| state.set_light(window_id, &name, descriptor))?;
+            }
+            CustomUserEvent::DeleteLight(window_id, name) => {
+                self.with_state(|state| state.delete_light(window_id, &name))?;
+            }
+            CustomUserEvent::EnableShadows(window_id, light_name, settings) => {
+                self.with_state(|state| state.enable_shadows(window_id, &light_name, settings))?;
+            }
+            CustomUserEvent::DisableShadows(window_id, light_name) => {
+                self.with_state(|state| state.disable_shadows(window_id, &light_name))?;
+            }
+            CustomUserEvent::SetCastShadows(window_id, name, cast) => {
+                self.with_state(|state| state.set_cast_shadows(window_id, &name, cast))?;
+            }
+
+            CustomUserEvent::SetEnvironment(window_id, hdr_path) => {
+                self.with_state(|state| state.set_environment(window_id, &hdr_path))?;
+            }
+            CustomUserEvent::SetEnvironmentExposure(window_id, exposure) => {
+                self.with_state(|state| state.set_environment_exposure(window_id, exposure))?;
+            }
+            CustomUserEvent::SetEnvironmentRotation(window_id, angle) => {
+                self.with_state(|state| state.set_environment_rotation(window_id, angle))?;
+            }
+
+            CustomUserEvent::CreateMaterial(window_id, name, descriptor) => {
+                self.with_state(|state| state.create_material(window_id, name, descriptor))?;
+            }
+            CustomUserEvent::SetMaterial(window_id, name, descriptor) => {
+                self.with_state(|state| state.set_material(window_id, &name, descriptor))?;
+            }
+            CustomUserEvent::DeleteMaterial(window_id, name) => {
+                self.with_state(|state| state.delete_material(window_id, &name))?;
+            }
+            CustomUserEvent::AssignMaterial(window_id, object, material_name) => {
+                self.with_state(|state| state.assign_material(window_id, &object, &material_name))?;
+            }
+            CustomUserEvent::SetImageTint(window_id, name, color) => {
+                self.with_state(|state| state.set_image_tint(window_id, &name, color))?;
+            }
+
+            CustomUserEvent::SetLayer(window_id, name, order, ignore_depth) => {
+                self.with_state(|state| state.set_layer(window_id, name, order, ignore_depth))?;
+            }
+            CustomUserEvent::DeleteLayer(window_id, name) => {
+                self.with_state(|state| state.delete_layer(window_id, &name))?;
+            }
+            CustomUserEvent::SetImageLayer(window_id, name, layer) => {
+                self.with_state(|state| state.set_image_layer(window_id, &name, &layer))?;
+            }
+            CustomUserEvent::SetImageZOrder(window_id, name, z_order) => {
+                self.with_state(|state| state.set_image_z_order(window_id, &name, z_order))?;
+            }
+            CustomUserEvent::SetImageBlendMode(window_id, name, mode) => {
+                self.with_state(|state| state.set_image_blend_mode(window_id, &name, mode))?;
+            }
+            CustomUserEvent::SetImageOpacity(window_id, name, opacity, duration) => {
+                self.with_state(|state| {
+                    state.set_image_opacity(window_id, &name, opacity, duration)
+                })?;
+            }
+            CustomUserEvent::SetImageVisible(window_id, name, visible) => {
+                self.with_state(|state| state.set_image_visible(window_id, &name, visible))?;
+            }
+            CustomUserEvent::SetLayerVisible(window_id, name, visible) => {
+                self.with_state(|state| state.set_layer_visible(window_id, &name, visible))?;
+            }
+            CustomUserEvent::SetImageHitAlpha(window_id, name, threshold) => {
+                self.with_state(|state| state.set_image_hit_alpha(window_id, &name, threshold))?;
+            }
+
+            CustomUserEvent::SetParent(window_id, child, parent) => {
+                self.with_state(|state| state.set_parent(window_id, &child, &parent))?;
+            }
+            CustomUserEvent::DetachFromParent(window_id, child) => {
+                self.with_state(|state| state.detach_from_parent(window_id, &child))?;
+            }
+            CustomUserEvent::DeleteObject(window_id, name, recursive) => {
+                self.with_state(|state| state.delete_object(window_id, &name, recursive))?;
+            }
+
+            CustomUserEvent::SaveScene(window_id, path) => {
+                self.with_state(|state| state.save_scene(window_id, &path))?;
+            }
+            CustomUserEvent::LoadScene(window_id, path) => {
+                self.with_state(|state| state.load_scene(window_id, &path))?;
+            }
+
+            CustomUserEvent::SetClosePolicy(window_id, policy) => {
+                self.with_state(|state| state.set_close_policy(window_id, policy))?;
+            }
+            CustomUserEvent::SetWindowPosition(window_id, x, y) => {
+                self.with_state(|state| state.set_window_position(window_id, x, y))?;
+            }
+            CustomUserEvent::SetWindowSize(window_id, width, height) => {
+                self.with_state(|state| state.set_window_size(window_id, width, height))?;
+            }
+            CustomUserEvent::SetWindowTitle(window_id, title) => {
+                self.with_state(|state| state.set_window_title(window_id, &title))?;
+            }
+            CustomUserEvent::SetFullscreen(window_id, mode) => {
+                self.with_state(|state| state.set_fullscreen(window_id, mode))?;
+            }
+            CustomUserEvent::SetDecorations(window_id, decorations) => {
+                self.with_state(|state| state.set_decorations(window_id, decorations))?;
+            }
+            CustomUserEvent::SetAlwaysOnTop(window_id, always_on_top) => {
+                self.with_state(|state| state.set_always_on_top(window_id, always_on_top))?;
+            }
+            CustomUserEvent::SetVisible(window_id, visible) => {
+                self.with_state(|state| state.set_visible(window_id, visible))?;
+            }
+            CustomUserEvent::SetCursorVisible(window_id, visible) => {
+                self.with_state(|state| state.set_cursor_visible(window_id, visible))?;
+            }
+            CustomUserEvent::SetCursorIcon(window_id, icon) => {
+                self.with_state(|state| state.set_cursor_icon(window_id, &icon))?;
+            }
+            CustomUserEvent::Minimize(window_id, minimized) => {
+                self.with_state(|state| state.minimize(window_id, minimized))?;
+            }
+            CustomUserEvent::SetRedrawPolicy(window_id, policy) => {
+                self.with_state(|state| state.set_redraw_policy(window_id, policy))?;
+            }
+            CustomUserEvent::Maximize(window_id, maximized) => {
+                self.with_state(|state| state.maximize(window_id, maximized))?;
+            }
+
+            CustomUserEvent::Command(command, responder) => {
+                let creates_window = matches!(
+                    command,
+                    Command::CreateWindow { .. } | Command::AttachCanvas { .. }
+                );
+                let id = command.window();
+                let result = self.handle_event(event_loop, command.into_event());
+                if let Some(responder) = responder {
+                    // w przeglądarce okno jest gotowe dopiero po WindowReady
+                    if cfg!(target_arch = "wasm32") && creates_window && result.is_ok() {
+                        self.pending_windows.insert(id, responder);
+                    } else {
+                        responder(Reply::from_result(&result));
+                    }
+                }
+                return result;
+            }
+        }
+        if let Some(id) = target {
+            if let Some(ws) = self
+                .state
+                .write()
+                .unwrap()
+                .as_mut()
+                .and_then(|state| state.windows.get_mut(&id))
+            {
+                ws.needs_redraw = true;
+            }
+        }
+        Ok(None)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn attach_canvas(
+        &mut self,
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        id: u32,
+        canvas_id: &str,
+        background_color: palette::Srgba,
+    ) -> anyhow::Result<()> {
+        use winit::platform::web::WindowAttributesExtWebSys;
+
+        let canvas = canvas::find_canvas(canvas_id)?;
+        let window_attributes =
+            winit::window::Window::default_attributes().with_canvas(Some(canvas));
+        self.spawn_canvas_window(event_loop, id, window_attributes, background_color)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn attach_canvas(
+        &mut self,
+        _event_loop: &winit::event_loop::ActiveEventLoop,
+        id: u32,
+        canvas_id: &str,
+        _background_color: palette::Srgba,
+    ) -> anyhow::Result<()> {
+        Err(anyhow::anyhow!(
+            "Unable to attach canvas '{}' to window {} outside the browser",
+            canvas_id,
+            id
+        ))
+    }
+
+    // Kontekst GPU w przeglądarce powstaje asynchronicznie – okno trafia do `State` przez WindowReady
+    #[cfg(target_arch = "wasm32")]
+    fn spawn_canvas_window(
+        &mut self,
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        id: u32,
+        window_attributes: winit::window::WindowAttributes,
+        background_color: palette::Srgba,
+    ) -> anyhow::Result<()> {
+        self.with_state(|state| state.ensure_window_id_free(id))?;
+        let window = std::sync::Arc::new(event_loop.create_window(window_attributes)?);
+        let proxy = self.proxy.clone();
+        let gpu = self.with_state(|state| Ok(state.gpu.clone()))?;
+        wasm_bindgen_futures::spawn_local(async move {
+            let mut size = window.inner_size();
+            while size.width == 0 || size.height == 0 {
+                req_animation_frame::next_animation_frame().await;
+                size = window.inner_size();
+            }
+            let ws = WindowState::new(&gpu, window, background_color).await;
+            let _ = proxy.send_event(CustomUserEvent::WindowReady(id, ws));
+        });
+        Ok(())
+    }
+
+    // Odtwarzanie, w którym żadne okno nie jest na ekranie – system nie wyśle `RedrawRequested`
+    fn replay_without_redraw(&self) -> bool {
+        self.replay.is_some()
+            && self
+                .with_state(|state| Ok(!state.any_window_shown(self.hidden_windows)))
+                .unwrap_or(false)
+    }
+
+    // Przesuwa klatkę nagrania bez renderowania, gdy okna są ukryte
+    fn step_replay_without_redraw(&mut self, event_loop: &ActiveEventLoop) {
+        // po końcu nagrania zegar stoi na ostatniej klatce
+        if let Some(time) = self.replay.as_ref().and_then(|r| r.frame_time(self.frame)) {
+            set_frame_clock(Some(time));
+        }
+        let now = now_seconds();
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.record_frame(self.frame, now);
+        }
+        self.frame += 1;
+        let frame = self.frame;
+        let result = self.with_state(|state| {
+            for (&id, ws) in state.windows.iter_mut() {
+                let dt = ws.advance_frame(now);
+                events::emit(OutboundEvent::FrameTick {
+                    window: id,
+                    frame,
+                    dt,
+                });
+            }
+            Ok(())
+        });
+        if let Err(e) = result {
+            log::error!("{e}");
+        }
+        event_loop.set_control_flow(winit::event_loop::ControlFlow::Poll);
+    }
+
+    // Prosi o klatki według polityk okien i usypia pętlę do najbliższej zaplanowanej
+    fn schedule_redraws(&self, event_loop: &ActiveEventLoop) {
+        // nagranie odtwarzamy klatka po klatce, więc wtedy każde okno renderuje bez przerw
+        let replaying = self.replay.is_some();
+        let now = now_seconds();
+        let wake = self.with_state(|state| {
+            let mut wake: Option<f64> = None;
+            for ws in state.windows.values() {
+                let next = if replaying {
+                    Some(now)
+                } else {
+                    ws.next_redraw()
+                };
+                match next {
+                    Some(at) if at <= now => ws.window.request_redraw(),
+                    Some(at) => wake = Some(wake.map_or(at, |wake| wake.min(at))),
+                    None => {}
+                }
+            }
+            Ok(wake)
+        });
+        let control_flow = match wake {
+            std::result::Result::Ok(Some(at)) => winit::event_loop::ControlFlow::WaitUntil(
+                web_time::Instant::now() + std::time::Duration::from_secs_f64(at - now),
+            ),
+            _ => winit::event_loop::ControlFlow::Wait,
+        };
+        event_loop.set_control_flow(control_flow);
+    }
+
+    fn with_state<T>(&self, f: impl FnOnce(&mut State) -> anyhow::Result<T>) -> anyhow::Result<T> {
+        let mut state = self.state.write().unwrap();
+        let state = state
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("State is not initialized"))?;
+        f(state)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    // plik z timeline (.ron / .json); None = wbudowane demo ze `scripts/demo.ron`
+    pub script: Option<String>,
+    // nagrywa obsłużone komendy do pliku JSON lines
+    pub record: Option<String>,
+    // odtwarza nagranie zamiast skryptu/demo
+    pub replay: Option<String>,
+    pub hidden_windows: bool,
+    // komendy JSON lines ze stdin, odpowiedzi na stdout; wyłącza demo
+    pub stdin: bool,
+    // serwer z tym samym protokołem: `127.0.0.1:7878` albo `unix:/ścieżka`; wyłącza demo
+    pub listen: Option<String>,
+    // serwer TCP słucha poza loopbackiem – protokół nie ma uwierzytelniania
+    pub allow_remote: bool,
+    // katalog, w którym klienci serwera zapisują i wczytują sceny; None = katalog bieżący
+    pub scene_dir: Option<String>,
+    // wasm: WebSocket z tym samym protokołem, np. `ws://localhost:7878`; wyłącza demo
+    #[cfg(target_arch = "wasm32")]
+    pub remote: Option<String>,
+    // wasm: id canvasu okna 0; None = bez podpinania do strony
+    #[cfg(target_arch = "wasm32")]
+    pub canvas: Option<String>,
+}
+
 pub fn run() -> anyhow::Result<()> {
+    run_with(RunOptions::default())
+}
+
+pub fn run_with(options: RunOptions) -> anyhow::Result<()> {
     let event_loop = winit::event_loop::EventLoop::<CustomUserEvent>::with_user_event().build()?;
     let mut app = App::new(&event_loop);
+    app.hidden_windows = options.hidden_windows;
+    #[cfg(target_arch = "wasm32")]
+    {
+        app.canvas = options.canvas.clone();
+    }
 
     set_global_proxy(app.proxy.clone());
 
@@ -887,16 +1693,49 @@ pub fn run() -> anyhow::Result<()> {
     #[cfg(target_arch = "wasm32")]
     console_log::init_with_level(log::Level::Info).unwrap();
 
+    if let Some(path) = &options.record {
+        app.recorder = Some(Recorder::create(path)?);
+    }
+    if let Some(path) = &options.replay {
+        app.replay = Some(Replay::load(path)?);
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
-    {
-        std::thread::spawn(move || {
-            start();
-        });
+    if options.stdin {
+        protocol::serve_stdin(app.proxy.clone());
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(addr) = &options.listen {
+        server::serve(addr, &options)?;
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    match options.script {
+        _ if app.replay.is_some() => {}
+        Some(path) => play_timeline(Timeline::load(&path)?, app.proxy.clone()),
+        None if options.stdin || options.listen.is_some() => {}
+        None => play_timeline(Timeline::demo()?, app.proxy.clone()),
     }
     #[cfg(target_arch = "wasm32")]
     {
+        if let Some(url) = &options.remote {
+            remote::connect(url)?;
+        }
+        let proxy = app.proxy.clone();
+        let replaying = app.replay.is_some();
+        let remote = options.remote.is_some();
         wasm_bindgen_futures::spawn_local(async move {
-            async_start().await;
+            match options.script {
+                _ if replaying => {}
+                Some(url) => match Timeline::fetch(&url).await {
+                    std::result::Result::Ok(timeline) => play_timeline(timeline, proxy),
+                    Err(e) => log::error!("{e}"),
+                },
+                None if remote => {}
+                None => match Timeline::demo() {
+                    std::result::Result::Ok(timeline) => play_timeline(timeline, proxy),
+                    Err(e) => log::error!("{e}"),
+                },
+            }
         });
     }
 
@@ -909,96 +1748,13 @@ pub fn run() -> anyhow::Result<()> {
 pub fn run_web() {
     use wasm_bindgen::UnwrapThrowExt;
     console_error_panic_hook::set_once();
-    run().unwrap_throw();
-}
-
-fn start() {
-    let proxy = get_global_proxy().unwrap();
-    let proxy = std::sync::Arc::new(proxy.clone());
-
-    #[cfg(not(target_arch = "wasm32"))]
-    std::thread::spawn(move || {
-        let _ = proxy.send_event(CustomUserEvent::CreateWindow(
-            0,
-            800,
-            600,
-            "test".to_string(),
-            palette::Srgba::new(0.1, 0.2, 0.3, 1.0),
-            true,
-        ));
-        #[cfg(not(target_arch = "wasm32"))]
-        std::thread::sleep(std::time::Duration::from_secs(2));
-        let _ = proxy.send_event(CustomUserEvent::LoadTexture(
-            0,
-            "assets/obraz.png".to_string(),
-        ));
-        let _ = proxy.send_event(CustomUserEvent::LoadTexture(0, "assets/a.png".to_string()));
-        #[cfg(not(target_arch = "wasm32"))]
-        std::thread::sleep(std::time::Duration::from_secs(1));
-        let _ = proxy.send_event(CustomUserEvent::AddImage(
-            0,
-            "test".to_string(),
-            "assets/obraz.png".to_string(),
-            0.0,
-            0.0,
-            0.0,
-        ));
-        let _ = proxy.send_event(CustomUserEvent::AddImage(
-            0,
-            "test2".to_string(),
-            "assets/a.png".to_string(),
-            0.0,
-            -15.0,
-            0.0,
-        ));
-
-        std::thread::sleep(std::time::Duration::from_secs(5));
-        let mut x = 0.0;
-        let mut dx = 0.05;
-
-        loop {
-            let _ = proxy.send_event(CustomUserEvent::SetImageRotation(
-                0,
-                "test".to_string(),
-                x / 10.0,
-            ));
-            let _ = proxy.send_event(CustomUserEvent::SetImageSize(
-                0,
-                "test2".to_string(),
-                196,
-                ((x + 5.0) * 1000.0) as u32,
-            ));
-
-            x += dx;
-
-            // zmiana kierunku po osiągnięciu granic
-            if x >= 2.0 {
-                dx = -dx;
-            } else if x <= -2.0 {
-                dx = -dx;
-            }
-            std::thread::sleep(std::time::Duration::from_millis(100));
-        }
-    });
-}
-
-async fn async_start() {
-    log::info!("DZIAŁA!");
-    sleep_for(500).await;
-    let proxy = get_global_proxy().unwrap();
-    let proxy = std::sync::Arc::new(proxy.clone());
-
-    sleep_for(2000).await;
-    let _ = proxy.send_event(CustomUserEvent::LoadTexture(0, "assets/a.png".to_string()));
-    sleep_for(1000).await;
-    let _ = proxy.send_event(CustomUserEvent::AddImage(
-        0,
-        "test".to_string(),
-        "assets/a.png".to_string(),
-        0.0,
-        0.0,
-        0.0,
-    ));
+    run_with(RunOptions {
+        script: timeline::script_from_url(),
+        remote: remote::remote_from_url(),
+        canvas: Some(canvas::canvas_from_url().unwrap_or_else(|| WASM_CANVAS_ID.to_string())),
+        ..Default::default()
+    })
+    .unwrap_throw();
 }
 
 pub async fn sleep_for(ms: u32) {
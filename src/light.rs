@@ -0,0 +1,481 @@
+//! Directional, point and spot lights managed per window.
+use craballoc::value::Hybrid;
+use renderling::{
+    pbr::light::{DirectionalLight, Light, PointLight, ShadowMap, SpotLight},
+    stage::Renderlet,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{ImageStage, State, WindowState};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum LightDescriptor {
+    Directional {
+        direction: [f32; 3],
+        color: [f32; 4],
+        intensity: f32,
+    },
+    // renderling wygasza światło z odwrotnością kwadratu odległości, bez odcięcia, więc
+    // `range` nie ogranicza oświetlenia: przy świetle punktowym jest odrzucany, przy
+    // reflektorze wyznacza tylko zasięg mapy cieni
+    Point {
+        position: [f32; 3],
+        color: [f32; 4],
+        intensity: f32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        range: Option<f32>,
+    },
+    Spot {
+        position: [f32; 3],
+        direction: [f32; 3],
+        inner_cutoff: f32,
+        outer_cutoff: f32,
+        color: [f32; 4],
+        intensity: f32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        range: Option<f32>,
+    },
+}
+
+// Światło kierunkowe (i reflektor bez `range`) nie ma zasięgu, więc mapa cieni obejmuje
+// stały fragment sceny
+const DEFAULT_SHADOW_DISTANCE: f32 = 100.0;
+const SHADOW_Z_NEAR: f32 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShadowSettings {
+    pub resolution: u32,
+    pub bias_min: f32,
+    pub bias_max: f32,
+    // liczba próbek PCF – 1 oznacza twarde krawędzie cienia
+    pub pcf_samples: u32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            resolution: 1024,
+            bias_min: 0.0005,
+            bias_max: 0.005,
+            pcf_samples: 4,
+        }
+    }
+}
+
+pub enum LightHandle {
+    Directional(Hybrid<DirectionalLight>),
+    Point(Hybrid<PointLight>),
+    Spot(Hybrid<SpotLight>),
+}
+
+pub struct LightObject {
+    pub descriptor: LightDescriptor,
+    pub light: LightHandle,
+    pub link: Hybrid<Light>,
+    pub shadow: Option<(ShadowSettings, ShadowMap)>,
+}
+
+impl LightHandle {
+    fn new(ws: &WindowState, descriptor: &LightDescriptor) -> Self {
+        match *descriptor {
+            LightDescriptor::Directional {
+                direction,
+                color,
+                intensity,
+            } => LightHandle::Directional(ws.stage.new_value(DirectionalLight {
+                direction: glam::Vec3::from(direction).normalize_or_zero(),
+                color: color.into(),
+                intensity,
+            })),
+            LightDescriptor::Point {
+                position,
+                color,
+                intensity,
+                ..
+            } => LightHandle::Point(ws.stage.new_value(PointLight {
+                position: position.into(),
+                color: color.into(),
+                intensity,
+            })),
+            LightDescriptor::Spot {
+                position,
+                direction,
+                inner_cutoff,
+                outer_cutoff,
+                color,
+                intensity,
+                ..
+            } => LightHandle::Spot(ws.stage.new_value(SpotLight {
+                position: position.into(),
+                direction: glam::Vec3::from(direction).normalize_or_zero(),
+                inner_cutoff,
+                outer_cutoff,
+                color: color.into(),
+                intensity,
+            })),
+        }
+    }
+
+    fn link(&self) -> Light {
+        match self {
+            LightHandle::Directional(light) => Light::from(light.id()),
+            LightHandle::Point(light) => Light::from(light.id()),
+            LightHandle::Spot(light) => Light::from(light.id()),
+        }
+    }
+
+    // Zwraca false, jeśli rodzaj światła się zmienił i trzeba je utworzyć od nowa
+    fn update(&self, descriptor: &LightDescriptor) -> bool {
+        match (self, *descriptor) {
+            (
+                LightHandle::Directional(light),
+                LightDescriptor::Directional {
+                    direction,
+                    color,
+                    intensity,
+                },
+            ) => {
+                light.set(DirectionalLight {
+                    direction: glam::Vec3::from(direction).normalize_or_zero(),
+                    color: color.into(),
+                    intensity,
+                });
+                true
+            }
+            (
+                LightHandle::Point(light),
+                LightDescriptor::Point {
+                    position,
+                    color,
+                    intensity,
+                    ..
+                },
+            ) => {
+                light.set(PointLight {
+                    position: position.into(),
+                    color: color.into(),
+                    intensity,
+                });
+                true
+            }
+            (
+                LightHandle::Spot(light),
+                LightDescriptor::Spot {
+                    position,
+                    direction,
+                    inner_cutoff,
+                    outer_cutoff,
+                    color,
+                    intensity,
+                    ..
+                },
+            ) => {
+                light.set(SpotLight {
+                    position: position.into(),
+                    direction: glam::Vec3::from(direction).normalize_or_zero(),
+                    inner_cutoff,
+                    outer_cutoff,
+                    color: color.into(),
+                    intensity,
+                });
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl LightDescriptor {
+    fn shadow_z_far(&self) -> Option<f32> {
+        match *self {
+            LightDescriptor::Directional { .. } => Some(DEFAULT_SHADOW_DISTANCE),
+            LightDescriptor::Spot { range, .. } => Some(range.unwrap_or(DEFAULT_SHADOW_DISTANCE)),
+            LightDescriptor::Point { .. } => None,
+        }
+    }
+
+    fn validate(&self, name: &str) -> anyhow::Result<()> {
+        match *self {
+            LightDescriptor::Point {
+                range: Some(range), ..
+            }
+            | LightDescriptor::Spot {
+                range: Some(range), ..
+            } if range.is_nan() || range <= 0.0 => Err(anyhow::anyhow!(
+                "Light '{}' has invalid range {}, it must be positive",
+                name,
+                range
+            )),
+            LightDescriptor::Point { range: Some(_), .. } => Err(anyhow::anyhow!(
+                "Point light '{}' can't have a range: renderling has no light cutoff distance",
+                name
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl WindowState {
+    // Ustawia nowy opis światła; przy zmianie rodzaju zwraca poprzedni uchwyt, żeby dało się
+    // go przywrócić
+    fn replace_light(&mut self, name: &str, descriptor: LightDescriptor) -> Option<LightHandle> {
+        let previous = if self.lights[name].light.update(&descriptor) {
+            None
+        } else {
+            // zmienił się rodzaj światła – nowy uchwyt, ten sam link
+            let handle = LightHandle::new(self, &descriptor);
+            let light = self.lights.get_mut(name).unwrap();
+            light.link.set(handle.link());
+            Some(std::mem::replace(&mut light.light, handle))
+        };
+        self.lights.get_mut(name).unwrap().descriptor = descriptor;
+        previous
+    }
+
+    fn restore_light(
+        &mut self,
+        name: &str,
+        descriptor: LightDescriptor,
+        handle: Option<LightHandle>,
+    ) {
+        let light = self.lights.get_mut(name).unwrap();
+        match handle {
+            Some(handle) => {
+                light.link.set(handle.link());
+                light.light = handle;
+            }
+            None => {
+                light.light.update(&descriptor);
+            }
+        }
+        light.descriptor = descriptor;
+    }
+
+    fn sync_lights(&self) {
+        self.stage
+            .set_lights(self.lights.values().map(|light| light.link.id()));
+    }
+
+    // Renderuje mapy cieni – wywoływane przed każdą klatką
+    pub fn update_shadows(&self) {
+        if self.lights.values().all(|light| light.shadow.is_none()) {
+            return;
+        }
+        let casters: Vec<&Hybrid<Renderlet>> = self
+            .models
+            .values()
+            .filter(|model| model.cast_shadows)
+            .flat_map(|model| model.renderlets.iter())
+            .chain(
+                self.images
+                    .values()
+                    .filter(|image| {
+                        image.cast_shadows
+                            && image.stage == ImageStage::Main
+                            && self.is_image_visible(image)
+                    })
+                    .map(|image| &image.renderlet),
+            )
+            .collect();
+        for light in self.lights.values() {
+            if let Some((_, shadow)) = &light.shadow {
+                shadow.update(&self.stage, casters.iter().copied());
+            }
+        }
+    }
+}
+
+impl State {
+    pub fn set_lighting(&mut self, window_id: u32, enabled: bool) -> anyhow::Result<()> {
+        let ws = self
+            .windows
+            .get_mut(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+        ws.stage.set_has_lighting(enabled);
+        ws.lighting = enabled;
+        Ok(())
+    }
+
+    pub fn add_light(
+        &mut self,
+        window_id: u32,
+        name: String,
+        descriptor: LightDescriptor,
+    ) -> anyhow::Result<()> {
+        let ws = self
+            .windows
+            .get_mut(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+
+        if ws.lights.contains_key(&name) {
+            return Err(anyhow::anyhow!("Light with name '{}' already exists", name));
+        }
+        descriptor.validate(&name)?;
+
+        let light = LightHandle::new(ws, &descriptor);
+        let link = ws.stage.new_value(light.link());
+        ws.lights.insert(
+            name,
+            LightObject {
+                descriptor,
+                light,
+                link,
+                shadow: None,
+            },
+        );
+        ws.sync_lights();
+        Ok(())
+    }
+
+    pub fn set_light(
+        &mut self,
+        window_id: u32,
+        name: &str,
+        descriptor: LightDescriptor,
+    ) -> anyhow::Result<()> {
+        let ws = self
+            .windows
+            .get_mut(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+        let light = ws
+            .lights
+            .get(name)
+            .ok_or_else(|| anyhow::anyhow!("No light with name '{}'", name))?;
+        descriptor.validate(name)?;
+        let shadow = light.shadow.as_ref().map(|(settings, _)| *settings);
+        if shadow.is_some() && descriptor.shadow_z_far().is_none() {
+            return Err(anyhow::anyhow!(
+                "Light '{}' casts shadows, which point lights don't support; disable them first",
+                name
+            ));
+        }
+        let previous = light.descriptor;
+        let previous_handle = ws.replace_light(name, descriptor);
+
+        // zasięg mapy cieni zależy od światła, więc tworzymy ją od nowa – stara zostaje, dopóki
+        // nowa nie powstanie, a przy błędzie wraca też poprzednie światło
+        if let Some(settings) = shadow {
+            if let Err(e) = self.enable_shadows(window_id, name, settings) {
+                let ws = self.windows.get_mut(&window_id).unwrap();
+                ws.restore_light(name, previous, previous_handle);
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn delete_light(&mut self, window_id: u32, name: &str) -> anyhow::Result<()> {
+        let ws = self
+            .windows
+            .get_mut(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+        ws.lights
+            .remove(name)
+            .ok_or_else(|| anyhow::anyhow!("No light with name '{}'", name))?;
+        ws.sync_lights();
+        Ok(())
+    }
+
+    pub fn enable_shadows(
+        &mut self,
+        window_id: u32,
+        light_name: &str,
+        settings: ShadowSettings,
+    ) -> anyhow::Result<()> {
+        let ws = self
+            .windows
+            .get_mut(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+        let light = ws
+            .lights
+            .get_mut(light_name)
+            .ok_or_else(|| anyhow::anyhow!("No light with name '{}'", light_name))?;
+
+        let z_far = light.descriptor.shadow_z_far().ok_or_else(|| {
+            anyhow::anyhow!(
+                "Light '{}' is a point light, shadows are supported only for directional and spot lights",
+                light_name
+            )
+        })?;
+
+        let shadow = ws
+            .stage
+            .new_shadow_map(
+                &light.link,
+                glam::UVec2::splat(settings.resolution.max(1)),
+                SHADOW_Z_NEAR,
+                z_far,
+            )
+            .map_err(|e| anyhow::anyhow!("Failed to create shadow map: {:?}", e))?;
+        shadow.shadowmap_descriptor.modify(|desc| {
+            desc.bias_min = settings.bias_min;
+            desc.bias_max = settings.bias_max;
+            desc.pcf_samples = settings.pcf_samples.max(1);
+        });
+        light.shadow = Some((settings, shadow));
+        Ok(())
+    }
+
+    pub fn disable_shadows(&mut self, window_id: u32, light_name: &str) -> anyhow::Result<()> {
+        let ws = self
+            .windows
+            .get_mut(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+        let light = ws
+            .lights
+            .get_mut(light_name)
+            .ok_or_else(|| anyhow::anyhow!("No light with name '{}'", light_name))?;
+        light.shadow = None;
+        Ok(())
+    }
+
+    pub fn set_cast_shadows(
+        &mut self,
+        window_id: u32,
+        name: &str,
+        cast: bool,
+    ) -> anyhow::Result<()> {
+        let ws = self
+            .windows
+            .get_mut(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+        if let Some(model) = ws.models.get_mut(name) {
+            model.cast_shadows = cast;
+        } else if let Some(image) = ws.images.get_mut(name) {
+            image.cast_shadows = cast;
+        } else {
+            return Err(anyhow::anyhow!("No object with name '{}'", name));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn point_light_range_is_rejected() {
+        let point = |range| LightDescriptor::Point {
+            position: [0.0, 1.0, 0.0],
+            color: [1.0, 1.0, 1.0, 1.0],
+            intensity: 1.0,
+            range,
+        };
+        assert!(point(None).validate("lamp").is_ok());
+        assert!(point(Some(5.0)).validate("lamp").is_err());
+
+        let spot = LightDescriptor::Spot {
+            position: [0.0, 1.0, 0.0],
+            direction: [0.0, -1.0, 0.0],
+            inner_cutoff: 0.2,
+            outer_cutoff: 0.4,
+            color: [1.0, 1.0, 1.0, 1.0],
+            intensity: 1.0,
+            range: Some(5.0),
+        };
+        assert!(spot.validate("lamp").is_ok());
+        assert_eq!(spot.shadow_z_far(), Some(5.0));
+    }
+}
@@ -1,5 +1,20 @@
-use renderling_test::run;
+use renderling_test::{run_with, RunOptions};
 
+// renderling_test_bin [skrypt.ron | skrypt.json] [--record plik.jsonl] [--replay plik.jsonl] [--hidden] [--stdin] [--listen adres] [--allow-remote] [--scene-dir katalog]
 fn main() {
-    run().unwrap();
-}
\ No newline at end of file
+    let mut options = RunOptions::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--record" => options.record = args.next(),
+            "--replay" => options.replay = args.next(),
+            "--hidden" => options.hidden_windows = true,
+            "--stdin" => options.stdin = true,
+            "--listen" => options.listen = args.next(),
+            "--allow-remote" => options.allow_remote = true,
+            "--scene-dir" => options.scene_dir = args.next(),
+            _ => options.script = Some(arg),
+        }
+    }
+    run_with(options).unwrap();
+}
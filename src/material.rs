@@ -0,0 +1,269 @@
+//! Named PBR materials that can be shared between images and models.
+use craballoc::value::Hybrid;
+use renderling::{atlas::AtlasTexture, pbr::Material};
+use serde::{Deserialize, Serialize};
+
+use crate::{ImageStage, State, WindowState};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaterialDescriptor {
+    // kolor bazowy mnożony przez teksturę (tint), alpha w ostatniej składowej
+    pub albedo_color: [f32; 4],
+    pub albedo_texture: Option<String>,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub emissive: [f32; 3],
+    pub emissive_strength: f32,
+    pub normal_map: Option<String>,
+    pub occlusion_map: Option<String>,
+    pub occlusion_strength: f32,
+    pub double_sided: bool,
+    pub unlit: bool,
+}
+
+impl Default for MaterialDescriptor {
+    fn default() -> Self {
+        Self {
+            albedo_color: [1.0, 1.0, 1.0, 1.0],
+            albedo_texture: None,
+            metallic: 0.0,
+            roughness: 1.0,
+            emissive: [0.0, 0.0, 0.0],
+            emissive_strength: 1.0,
+            normal_map: None,
+            occlusion_map: None,
+            occlusion_strength: 1.0,
+            double_sided: false,
+            unlit: false,
+        }
+    }
+}
+
+pub struct NamedMaterial {
+    pub descriptor: MaterialDescriptor,
+    pub material: Hybrid<Material>,
+}
+
+impl WindowState {
+    // Obrazek dostaje kopię materiału nazwanego we własnym materiale, więc tint i opacity
+    // zostają per obrazek. Bez `albedo_texture` w opisie zostaje mu jego własna tekstura.
+    // Tekstury materiału leżą w atlasie głównego stage – poza nim albedo ładujemy do atlasu
+    // stage'a obrazka, a mapy normalnych/AO pomijamy
+    pub fn apply_named_material(
+        &mut self,
+        image_name: &str,
+        material_name: &str,
+    ) -> anyhow::Result<()> {
+        let (Some(named), Some(image)) = (
+            self.materials.get(material_name),
+            self.images.get(image_name),
+        ) else {
+            return Ok(());
+        };
+        let mut mat = named.material.get();
+        let (stage, blend) = (image.stage, image.blend);
+        let albedo = match (&named.descriptor.albedo_texture, stage) {
+            (Some(_), ImageStage::Main) => None,
+            (Some(path), _) => Some(path.clone()),
+            (None, _) => Some(image.texture_path.clone()),
+        };
+        if let Some(path) = albedo {
+            mat.albedo_texture_id = self.stage_texture(stage, &path, blend)?.id();
+        }
+        if stage != ImageStage::Main {
+            mat.normal_texture_id = Default::default();
+            mat.ao_texture_id = Default::default();
+        }
+        let named = &self.materials[material_name];
+        let image = self.images.get_mut(image_name).unwrap();
+        image.material.set(mat);
+        image.material_name = Some(material_name.to_string());
+        image.base_color = named.descriptor.albedo_color;
+        image.apply_color();
+
+        let double_sided = named.descriptor.double_sided;
+        if image.double_sided != double_sided {
+            image.double_sided = double_sided;
+            self.upload_image(image_name);
+        }
+        Ok(())
+    }
+}
+
+impl State {
+    fn get_or_load_texture(
+        &mut self,
+        window_id: u32,
+        texture_path: &str,
+    ) -> anyhow::Result<Hybrid<AtlasTexture>> {
+        let loaded = self
+            .windows
+            .get(&window_id)
+            .and_then(|ws| ws.tex.get(texture_path).cloned());
+        match loaded {
+            Some(tex) => Ok(tex),
+            None => self.load_texture(window_id, texture_path),
+        }
+    }
+
+    fn build_material(
+        &mut self,
+        window_id: u32,
+        descriptor: &MaterialDescriptor,
+    ) -> anyhow::Result<Material> {
+        let mut mat = Material {
+            albedo_factor: descriptor.albedo_color.into(),
+            metallic_factor: descriptor.metallic,
+            roughness_factor: descriptor.roughness,
+            emissive_factor: descriptor.emissive.into(),
+            emissive_strength_multiplier: descriptor.emissive_strength,
+            ao_strength: descriptor.occlusion_strength,
+            has_lighting: !descriptor.unlit,
+            ..Default::default()
+        };
+        if let Some(path) = &descriptor.albedo_texture {
+            mat.albedo_texture_id = self.get_or_load_texture(window_id, path)?.id();
+        }
+        if let Some(path) = &descriptor.normal_map {
+            mat.normal_texture_id = self.get_or_load_texture(window_id, path)?.id();
+        }
+        if let Some(path) = &descriptor.occlusion_map {
+            mat.ao_texture_id = self.get_or_load_texture(window_id, path)?.id();
+        }
+        Ok(mat)
+    }
+
+    pub fn create_material(
+        &mut self,
+        window_id: u32,
+        name: String,
+        descriptor: MaterialDescriptor,
+    ) -> anyhow::Result<()> {
+        let mat = self.build_material(window_id, &descriptor)?;
+        let ws = self
+            .windows
+            .get_mut(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+        if ws.materials.contains_key(&name) {
+            return Err(anyhow::anyhow!(
+                "Material with name '{}' already exists",
+                name
+            ));
+        }
+        let material = ws.stage.new_material(mat);
+        ws.materials.insert(
+            name,
+            NamedMaterial {
+                descriptor,
+                material,
+            },
+        );
+        Ok(())
+    }
+
+    pub fn set_material(
+        &mut self,
+        window_id: u32,
+        name: &str,
+        descriptor: MaterialDescriptor,
+    ) -> anyhow::Result<()> {
+        let mat = self.build_material(window_id, &descriptor)?;
+        let ws = self
+            .windows
+            .get_mut(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+        let named = ws
+            .materials
+            .get_mut(name)
+            .ok_or_else(|| anyhow::anyhow!("No material with name '{}'", name))?;
+        named.material.set(mat);
+        named.descriptor = descriptor;
+
+        // obrazki mają własne kopie materiału – odświeżamy je
+        let users: Vec<String> = ws
+            .images
+            .iter()
+            .filter(|(_, image)| image.material_name.as_deref() == Some(name))
+            .map(|(image_name, _)| image_name.clone())
+            .collect();
+        for image_name in users {
+            ws.apply_named_material(&image_name, name)?;
+        }
+        Ok(())
+    }
+
+    pub fn delete_material(&mut self, window_id: u32, name: &str) -> anyhow::Result<()> {
+        let ws = self
+            .windows
+            .get_mut(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+        // obrazki mają kopie materiału, a modele wskazują na jego slot – wracają do materiałów
+        // z pliku, zanim uchwyt zostanie zwolniony
+        let named = ws
+            .materials
+            .remove(name)
+            .ok_or_else(|| anyhow::anyhow!("No material with name '{}'", name))?;
+        for image in ws.images.values_mut() {
+            if image.material_name.as_deref() == Some(name) {
+                image.material_name = None;
+            }
+        }
+        for model in ws.models.values_mut() {
+            if model.material_name.as_deref() == Some(name) {
+                model.set_named_material(None);
+            }
+        }
+        drop(named);
+        Ok(())
+    }
+
+    pub fn assign_material(
+        &mut self,
+        window_id: u32,
+        object: &str,
+        material_name: &str,
+    ) -> anyhow::Result<()> {
+        let ws = self
+            .windows
+            .get_mut(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+        let material_id = ws
+            .materials
+            .get(material_name)
+            .ok_or_else(|| anyhow::anyhow!("No material with name '{}'", material_name))?
+            .material
+            .id();
+
+        if ws.images.contains_key(object) {
+            ws.apply_named_material(object, material_name)?;
+        } else if let Some(model) = ws.models.get_mut(object) {
+            model.set_named_material(Some((material_name, material_id)));
+        } else {
+            return Err(anyhow::anyhow!("No object with name '{}'", object));
+        }
+        Ok(())
+    }
+
+    // Tint/alpha samego obrazka – bez nowej tekstury. Obrazek z materiałem nazwanym ma jego
+    // kopię, więc tint nie zmienia innych obiektów ani opisu materiału.
+    pub fn set_image_tint(
+        &mut self,
+        window_id: u32,
+        name: &str,
+        color: [f32; 4],
+    ) -> anyhow::Result<()> {
+        let ws = self
+            .windows
+            .get_mut(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+        let image = ws
+            .images
+            .get_mut(name)
+            .ok_or_else(|| anyhow::anyhow!("No image with name '{}'", name))?;
+        // opacity obrazka mnoży alpha z tintu
+        image.tint = color;
+        image.apply_color();
+        Ok(())
+    }
+}
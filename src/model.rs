@@ -0,0 +1,595 @@
+//! glTF/GLB models loaded into a window's stage as named scene objects.
+use craballoc::value::Hybrid;
+use renderling::{
+    pbr::Material,
+    prelude::Id,
+    stage::{GltfDocument, Renderlet, TweenProperty},
+    transform::{NestedTransform, Transform},
+};
+
+use crate::State;
+
+pub struct ModelObject {
+    pub path: String,
+    pub doc: GltfDocument,
+    pub renderlets: Vec<Hybrid<Renderlet>>,
+    // korzeń, pod który podpinamy wszystkie węzły główne dokumentu
+    pub root: NestedTransform,
+    pub position: glam::Vec3,
+    pub rotation: glam::Quat,
+    pub scale: glam::Vec3,
+    // lokalne transformacje węzłów z pliku – punkt wyjścia do mieszania animacji
+    pub rest_pose: Vec<Transform>,
+    pub animations: std::collections::HashMap<String, AnimationPlayback>,
+    pub cast_shadows: bool,
+    // materiał nazwany przypisany wszystkim renderletom modelu
+    pub material_name: Option<String>,
+    // materiały renderletów z pliku – wracają po usunięciu materiału nazwanego
+    pub gltf_materials: Vec<Id<Material>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AnimationPlayback {
+    pub index: usize,
+    pub time: f32,
+    pub speed: f32,
+    pub looping: bool,
+    pub playing: bool,
+    pub weight: f32,
+}
+
+impl AnimationPlayback {
+    // Klip odtwarzany wstecz zaczyna od końca – od zera `advance` zatrzymałby go od razu
+    pub fn new(index: usize, length: f32, speed: f32, looping: bool) -> Self {
+        Self {
+            index,
+            time: start_time(length, speed),
+            speed,
+            looping,
+            playing: true,
+            weight: 1.0,
+        }
+    }
+
+    // Ponowne uruchomienie: klip bez zapętlenia stojący na krawędzi, do której zmierza,
+    // wraca na swój początek; wstrzymany w środku gra dalej od miejsca pauzy
+    pub fn restart(&mut self, length: f32, speed: f32, looping: bool) {
+        let at_end = (speed > 0.0 && self.time >= length) || (speed < 0.0 && self.time <= 0.0);
+        if !looping && at_end {
+            self.time = start_time(length, speed);
+        }
+        self.speed = speed;
+        self.looping = looping;
+        self.playing = true;
+    }
+
+    // Klip bez zapętlenia kończy się dopiero na krawędzi, do której zmierza – klatka z dt = 0
+    // (pierwsza po starcie albo po bezczynności) nie zatrzymuje go na czasie 0
+    pub fn advance(&mut self, length: f32, dt: f32) {
+        let step = dt * self.speed;
+        self.time += step;
+        if length <= 0.0 {
+            self.time = 0.0;
+        } else if self.looping {
+            self.time = self.time.rem_euclid(length);
+        } else if (step > 0.0 && self.time >= length) || (step < 0.0 && self.time <= 0.0) {
+            self.time = self.time.clamp(0.0, length);
+            self.playing = false;
+        }
+    }
+}
+
+fn start_time(length: f32, speed: f32) -> f32 {
+    if speed < 0.0 {
+        length.max(0.0)
+    } else {
+        0.0
+    }
+}
+
+// Próbki kanałów jednego węzła ze wszystkich animacji: (wartość, waga)
+#[derive(Default)]
+struct NodeChannels {
+    translation: Vec<(glam::Vec3, f32)>,
+    rotation: Vec<(glam::Quat, f32)>,
+    scale: Vec<(glam::Vec3, f32)>,
+}
+
+// Średnia ważona próbek; do sumy wag 1 dopełnia ją poza spoczynkowa, powyżej 1 normalizujemy.
+// Wynik nie zależy od kolejności animacji
+fn blend_vec3(rest: glam::Vec3, samples: &[(glam::Vec3, f32)]) -> glam::Vec3 {
+    let total: f32 = samples.iter().map(|(_, w)| w).sum();
+    if total <= 0.0 {
+        return rest;
+    }
+    let rest_weight = (1.0 - total).max(0.0);
+    let sum = samples
+        .iter()
+        .fold(rest * rest_weight, |acc, (v, w)| acc + *v * *w);
+    sum / (rest_weight + total)
+}
+
+// To samo dla obrotów (nlerp): kwaterniony sprowadzamy na półsferę pozy spoczynkowej
+fn blend_quat(rest: glam::Quat, samples: &[(glam::Quat, f32)]) -> glam::Quat {
+    let total: f32 = samples.iter().map(|(_, w)| w).sum();
+    if total <= 0.0 {
+        return rest;
+    }
+    let rest_weight = (1.0 - total).max(0.0);
+    let sum = samples
+        .iter()
+        .fold(glam::Vec4::from(rest) * rest_weight, |acc, (q, w)| {
+            let q = if q.dot(rest) < 0.0 { -*q } else { *q };
+            acc + glam::Vec4::from(q) * *w
+        });
+    glam::Quat::from_vec4(sum).normalize()
+}
+
+impl ModelObject {
+    pub fn local_matrix(&self) -> glam::Mat4 {
+        glam::Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.position)
+    }
+
+    // `parent` to transformacja świata rodzica w hierarchii sceny
+    pub fn update_transform(&self, parent: glam::Mat4) {
+        let (scale, rotation, translation) =
+            (parent * self.local_matrix()).to_scale_rotation_translation();
+        self.root.set(Transform {
+            translation,
+            rotation,
+            scale,
+        });
+    }
+
+    pub fn animation_names(&self) -> Vec<String> {
+        self.doc
+            .animations
+            .iter()
+            .enumerate()
+            .map(|(i, anim)| {
+                anim.name
+                    .clone()
+                    .unwrap_or_else(|| format!("animation_{i}"))
+            })
+            .collect()
+    }
+
+    fn animation_index(&self, anim_name: &str) -> anyhow::Result<usize> {
+        self.animation_names()
+            .iter()
+            .position(|n| n == anim_name)
+            .ok_or_else(|| anyhow::anyhow!("No animation with name '{}'", anim_name))
+    }
+
+    pub fn is_animating(&self) -> bool {
+        self.animations.values().any(|a| a.playing)
+    }
+
+    // Materiał nazwany dla wszystkich renderletów albo, przy None, z powrotem te z pliku
+    pub fn set_named_material(&mut self, named: Option<(&str, Id<Material>)>) {
+        let ids = material_ids(&self.gltf_materials, named.map(|(_, id)| id));
+        for (renderlet, material_id) in self.renderlets.iter().zip(ids) {
+            let mut r = renderlet.get();
+            r.material_id = material_id;
+            renderlet.set(r);
+        }
+        self.material_name = named.map(|(name, _)| name.to_string());
+    }
+
+    pub fn advance_animations(&mut self, dt: f32) {
+        if !self.is_animating() {
+            return;
+        }
+        for playback in self.animations.values_mut() {
+            if !playback.playing {
+                continue;
+            }
+            let length = self.doc.animations[playback.index].length_in_seconds();
+            playback.advance(length, dt);
+        }
+        self.apply_pose();
+    }
+
+    // Składa pozę ze wszystkich aktywnych animacji, każda z własną wagą
+    fn apply_pose(&self) {
+        let mut pose = self.rest_pose.clone();
+        let mut channels: std::collections::HashMap<usize, NodeChannels> =
+            std::collections::HashMap::new();
+
+        for playback in self.animations.values() {
+            let animation = &self.doc.animations[playback.index];
+            let properties = match animation.get_properties_at_time(playback.time) {
+                Ok(properties) => properties,
+                Err(e) => {
+                    log::error!("Animation {} failed: {:?}", playback.index, e);
+                    continue;
+                }
+            };
+            let w = playback.weight.clamp(0.0, 1.0);
+            for (node_index, property) in properties {
+                let node = channels.entry(node_index).or_default();
+                match property {
+                    TweenProperty::Translation(v) => node.translation.push((v, w)),
+                    TweenProperty::Rotation(q) => node.rotation.push((q, w)),
+                    TweenProperty::Scale(v) => node.scale.push((v, w)),
+                    // wagi morph targetów nie są jeszcze obsługiwane
+                    TweenProperty::MorphTargetWeights(_) => {}
+                }
+            }
+        }
+
+        for (node_index, node) in channels {
+            let Some(t) = pose.get_mut(node_index) else {
+                continue;
+            };
+            t.translation = blend_vec3(t.translation, &node.translation);
+            t.rotation = blend_quat(t.rotation, &node.rotation);
+            t.scale = blend_vec3(t.scale, &node.scale);
+        }
+
+        for (node, t) in self.doc.nodes.iter().zip(pose) {
+            node.transform.set(t);
+        }
+    }
+}
+
+impl State {
+    pub fn load_model(
+        &mut self,
+        window_id: u32,
+        name: String,
+        model_path: String,
+        x: f32,
+        y: f32,
+        z: f32,
+    ) -> anyhow::Result<()> {
+        let ws = self
+            .windows
+            .get_mut(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+
+        ws.ensure_name_free(&name)?;
+
+        let doc = ws
+            .stage
+            .load_gltf_document_from_path(&model_path)
+            .map_err(|e| anyhow::anyhow!("Failed to load model '{}': {:?}", model_path, e))?;
+
+        // Węzły, które nie są niczyimi dziećmi, to korzenie sceny
+        let children: std::collections::HashSet<usize> = doc
+            .nodes
+            .iter()
+            .flat_map(|node| node.children.iter().copied())
+            .collect();
+        let root = ws.stage.new_nested_transform();
+        for (i, node) in doc.nodes.iter().enumerate() {
+            if !children.contains(&i) {
+                root.add_child(&node.transform);
+            }
+        }
+
+        let rest_pose = doc.nodes.iter().map(|node| node.transform.get()).collect();
+        let renderlets: Vec<Hybrid<Renderlet>> = doc.renderlets_iter().cloned().collect();
+        let gltf_materials = renderlets.iter().map(|r| r.get().material_id).collect();
+        for renderlet in &renderlets {
+            let mut r = renderlet.get();
+            r.camera_id = ws.main_camera.id();
+            renderlet.set(r);
+            ws.stage.add_renderlet(renderlet);
+        }
+
+        let model = ModelObject {
+            path: model_path,
+            doc,
+            renderlets,
+            root,
+            position: glam::Vec3::new(x, y, z),
+            rotation: glam::Quat::IDENTITY,
+            scale: glam::Vec3::ONE,
+            rest_pose,
+            animations: std::collections::HashMap::new(),
+            cast_shadows: true,
+            material_name: None,
+            gltf_materials,
+        };
+        model.update_transform(glam::Mat4::IDENTITY);
+
+        log::info!(
+            "Loaded model '{}' from '{}' with animations: {:?}",
+            name,
+            model.path,
+            model.animation_names()
+        );
+        ws.models.insert(name, model);
+        Ok(())
+    }
+
+    fn model_mut(&mut self, window_id: u32, name: &str) -> anyhow::Result<&mut ModelObject> {
+        let ws = self
+            .windows
+            .get_mut(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+        ws.models
+            .get_mut(name)
+            .ok_or_else(|| anyhow::anyhow!("No model with name '{}'", name))
+    }
+
+    fn update_model_transform(&mut self, window_id: u32, name: &str) {
+        if let Some(ws) = self.windows.get_mut(&window_id) {
+            ws.update_object_transform(name);
+        }
+    }
+
+    pub fn set_model_position(
+        &mut self,
+        window_id: u32,
+        name: &str,
+        x: f32,
+        y: f32,
+        z: f32,
+    ) -> anyhow::Result<()> {
+        let model = self.model_mut(window_id, name)?;
+        model.position = glam::Vec3::new(x, y, z);
+        self.update_model_transform(window_id, name);
+        Ok(())
+    }
+
+    pub fn set_model_rotation(
+        &mut self,
+        window_id: u32,
+        name: &str,
+        rot_x: f32,
+        rot_y: f32,
+        rot_z: f32,
+    ) -> anyhow::Result<()> {
+        let model = self.model_mut(window_id, name)?;
+        model.rotation = glam::Quat::from_euler(glam::EulerRot::XYZ, rot_x, rot_y, rot_z);
+        self.update_model_transform(window_id, name);
+        Ok(())
+    }
+
+    pub fn set_model_scale(
+        &mut self,
+        window_id: u32,
+        name: &str,
+        sx: f32,
+        sy: f32,
+        sz: f32,
+    ) -> anyhow::Result<()> {
+        let model = self.model_mut(window_id, name)?;
+        model.scale = glam::Vec3::new(sx, sy, sz);
+        self.update_model_transform(window_id, name);
+        Ok(())
+    }
+
+    pub fn delete_model(&mut self, window_id: u32, name: &str) -> anyhow::Result<()> {
+        let ws = self
+            .windows
+            .get_mut(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+
+        let model = ws
+            .models
+            .remove(name)
+            .ok_or_else(|| anyhow::anyhow!("No model with name '{}'", name))?;
+        for renderlet in &model.renderlets {
+            ws.stage.remove_renderlet(renderlet);
+        }
+        ws.unlink_object(name);
+
+        Ok(())
+    }
+
+    pub fn list_animations(&mut self, window_id: u32, model: &str) -> anyhow::Result<Vec<String>> {
+        Ok(self.model_mut(window_id, model)?.animation_names())
+    }
+
+    pub fn play_animation(
+        &mut self,
+        window_id: u32,
+        model: &str,
+        anim_name: &str,
+        speed: f32,
+        looping: bool,
+    ) -> anyhow::Result<()> {
+        let model = self.model_mut(window_id, model)?;
+        let index = model.animation_index(anim_name)?;
+        let length = model.doc.animations[index].length_in_seconds();
+        match model.animations.get_mut(anim_name) {
+            Some(playback) => playback.restart(length, speed, looping),
+            None => {
+                model.animations.insert(
+                    anim_name.to_string(),
+                    AnimationPlayback::new(index, length, speed, looping),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn pause_animation(
+        &mut self,
+        window_id: u32,
+        model: &str,
+        anim_name: &str,
+    ) -> anyhow::Result<()> {
+        let model = self.model_mut(window_id, model)?;
+        let playback = model
+            .animations
+            .get_mut(anim_name)
+            .ok_or_else(|| anyhow::anyhow!("Animation '{}' is not playing", anim_name))?;
+        playback.playing = false;
+        Ok(())
+    }
+
+    pub fn stop_animation(
+        &mut self,
+        window_id: u32,
+        model: &str,
+        anim_name: &str,
+    ) -> anyhow::Result<()> {
+        let model = self.model_mut(window_id, model)?;
+        model
+            .animations
+            .remove(anim_name)
+            .ok_or_else(|| anyhow::anyhow!("Animation '{}' is not playing", anim_name))?;
+        model.apply_pose();
+        Ok(())
+    }
+
+    pub fn seek_animation(
+        &mut self,
+        window_id: u32,
+        model: &str,
+        anim_name: &str,
+        time: f32,
+    ) -> anyhow::Result<()> {
+        let model = self.model_mut(window_id, model)?;
+        let index = model.animation_index(anim_name)?;
+        let length = model.doc.animations[index].length_in_seconds();
+        let playback = model
+            .animations
+            .entry(anim_name.to_string())
+            .or_insert(AnimationPlayback {
+                index,
+                time: 0.0,
+                speed: 1.0,
+                looping: false,
+                playing: false,
+                weight: 1.0,
+            });
+        playback.time = time.clamp(0.0, length.max(0.0));
+        model.apply_pose();
+        Ok(())
+    }
+
+    pub fn set_animation_weight(
+        &mut self,
+        window_id: u32,
+        model: &str,
+        anim_name: &str,
+        weight: f32,
+    ) -> anyhow::Result<()> {
+        let model = self.model_mut(window_id, model)?;
+        let playback = model
+            .animations
+            .get_mut(anim_name)
+            .ok_or_else(|| anyhow::anyhow!("Animation '{}' is not playing", anim_name))?;
+        playback.weight = weight;
+        model.apply_pose();
+        Ok(())
+    }
+}
+
+// Materiały renderletów modelu: nazwany dla wszystkich albo te z pliku
+fn material_ids(gltf: &[Id<Material>], named: Option<Id<Material>>) -> Vec<Id<Material>> {
+    gltf.iter().map(|id| named.unwrap_or(*id)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blend_is_independent_of_animation_order() {
+        let rest = glam::Vec3::ZERO;
+        let a = (glam::Vec3::new(2.0, 0.0, 0.0), 0.5);
+        let b = (glam::Vec3::new(0.0, 4.0, 0.0), 0.5);
+        let ab = blend_vec3(rest, &[a, b]);
+        let ba = blend_vec3(rest, &[b, a]);
+        assert_eq!(ab, ba);
+        assert_eq!(ab, glam::Vec3::new(1.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn blend_fills_missing_weight_with_rest_pose() {
+        let rest = glam::Vec3::ONE;
+        let blended = blend_vec3(rest, &[(glam::Vec3::new(3.0, 3.0, 3.0), 0.5)]);
+        assert_eq!(blended, glam::Vec3::splat(2.0));
+    }
+
+    #[test]
+    fn blend_normalizes_weights_above_one() {
+        let rotation = glam::Quat::from_rotation_y(1.0);
+        let blended = blend_quat(glam::Quat::IDENTITY, &[(rotation, 1.0), (rotation, 1.0)]);
+        assert!(blended.angle_between(rotation) < 1e-4);
+    }
+
+    fn started(speed: f32, looping: bool) -> AnimationPlayback {
+        AnimationPlayback::new(0, 2.0, speed, looping)
+    }
+
+    #[test]
+    fn first_frame_with_zero_dt_keeps_clip_playing() {
+        let mut playback = started(1.0, false);
+        playback.advance(2.0, 0.0);
+        assert!(playback.playing);
+        assert_eq!(playback.time, 0.0);
+        playback.advance(2.0, 0.5);
+        assert!(playback.playing);
+        assert_eq!(playback.time, 0.5);
+    }
+
+    #[test]
+    fn clip_stops_at_the_edge_it_moves_towards() {
+        let mut playback = started(1.0, false);
+        playback.advance(2.0, 3.0);
+        assert!(!playback.playing);
+        assert_eq!(playback.time, 2.0);
+
+        let mut reverse = started(-1.0, false);
+        reverse.time = 1.0;
+        reverse.advance(2.0, 0.5);
+        assert!(reverse.playing);
+        reverse.advance(2.0, 1.0);
+        assert!(!reverse.playing);
+        assert_eq!(reverse.time, 0.0);
+    }
+
+    #[test]
+    fn new_reverse_clip_plays_back_from_the_end() {
+        let mut reverse = started(-1.0, false);
+        assert_eq!(reverse.time, 2.0);
+        reverse.advance(2.0, 0.0);
+        assert!(reverse.playing);
+        reverse.advance(2.0, 0.5);
+        assert!(reverse.playing);
+        assert_eq!(reverse.time, 1.5);
+    }
+
+    #[test]
+    fn restarting_finished_clip_rewinds_to_its_start() {
+        let mut playback = started(1.0, false);
+        playback.advance(2.0, 3.0);
+        assert!(!playback.playing);
+        playback.restart(2.0, -1.0, false);
+        assert_eq!(playback.time, 2.0);
+        playback.advance(2.0, 3.0);
+        assert_eq!(playback.time, 0.0);
+        playback.restart(2.0, -1.0, false);
+        assert_eq!(playback.time, 2.0);
+
+        let mut paused = started(1.0, false);
+        paused.advance(2.0, 0.5);
+        paused.playing = false;
+        paused.restart(2.0, 1.0, false);
+        assert_eq!(paused.time, 0.5);
+    }
+
+    #[test]
+    fn looping_clip_wraps_around() {
+        let mut playback = started(1.0, true);
+        playback.advance(2.0, 2.5);
+        assert!(playback.playing);
+        assert_eq!(playback.time, 0.5);
+    }
+
+    #[test]
+    fn deleting_named_material_restores_gltf_materials() {
+        let gltf = [Id::<Material>::new(3), Id::new(7)];
+        let named = Id::new(42);
+        assert_eq!(material_ids(&gltf, Some(named)), [named, named]);
+        // po usunięciu materiału nazwanego renderlety nie mogą wskazywać na zwolniony slot
+        assert_eq!(material_ids(&gltf, None), gltf);
+    }
+}
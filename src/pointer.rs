@@ -0,0 +1,227 @@
+//! Pointer input: unprojecting the cursor and hit-testing sprites under it.
+use renderling::atlas::AtlasImage;
+
+use crate::{
+    events::{emit, OutboundEvent, PointerButton},
+    ImageObject, ImageStage, State, WindowState,
+};
+
+// Kanał alpha tekstury do testu trafienia, wczytywany przy pierwszym użyciu
+pub struct AlphaMask {
+    size: glam::UVec2,
+    alpha: Vec<u8>,
+}
+
+impl AlphaMask {
+    fn load(path: &str) -> anyhow::Result<Self> {
+        let image = AtlasImage::from_path(path)
+            .map_err(|e| anyhow::anyhow!("Failed to load texture '{}': {:?}", path, e))?;
+        let pixel_count = (image.size.x * image.size.y) as usize;
+        if image.pixels.len() != pixel_count * 4 {
+            return Err(anyhow::anyhow!(
+                "Alpha hit-testing is supported only for 8-bit RGBA textures: '{}'",
+                path
+            ));
+        }
+        Ok(Self {
+            size: image.size,
+            alpha: image.pixels.chunks_exact(4).map(|px| px[3]).collect(),
+        })
+    }
+
+    // `uv` z (0, 0) w lewym górnym rogu tekstury
+    fn alpha_at(&self, uv: glam::Vec2) -> f32 {
+        let x = ((uv.x * self.size.x as f32) as u32).min(self.size.x.saturating_sub(1));
+        let y = ((uv.y * self.size.y as f32) as u32).min(self.size.y.saturating_sub(1));
+        self.alpha[(y * self.size.x + x) as usize] as f32 / 255.0
+    }
+}
+
+impl ImageObject {
+    // Przecięcie promienia z prostokątem obrazka: (odległość, uv trafienia)
+    fn intersect(&self, origin: glam::Vec3, dir: glam::Vec3) -> Option<(f32, glam::Vec2)> {
+        self.vertices_cpu
+            .chunks_exact(3)
+            .take(2)
+            .filter_map(|tri| {
+                let (p0, p1, p2) = (tri[0].position, tri[1].position, tri[2].position);
+                // Möller–Trumbore, bez odrzucania tylnych ścian
+                let e1 = p1 - p0;
+                let e2 = p2 - p0;
+                let p = dir.cross(e2);
+                let det = e1.dot(p);
+                if det.abs() < f32::EPSILON {
+                    return None;
+                }
+                let s = origin - p0;
+                let u = s.dot(p) / det;
+                let q = s.cross(e1);
+                let v = dir.dot(q) / det;
+                if u < 0.0 || v < 0.0 || u + v > 1.0 {
+                    return None;
+                }
+                let t = e2.dot(q) / det;
+                if t < 0.0 {
+                    return None;
+                }
+                let uv = tri[0].uv0 * (1.0 - u - v) + tri[1].uv0 * u + tri[2].uv0 * v;
+                Some((t, uv))
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+    }
+}
+
+// Tryby mieszania i przebiegi overlay są nakładane na klatkę po głównym stage;
+// przebiegi overlay między sobą rozstrzyga kolejność rysowania (`draw_order`)
+fn stage_rank(stage: ImageStage) -> u8 {
+    match stage {
+        ImageStage::Main => 0,
+        ImageStage::Blend(_) => 1,
+        ImageStage::Overlay(_) => 2,
+    }
+}
+
+impl WindowState {
+    // Promień z kamery okna przez punkt ekranu (piksele fizyczne)
+    fn pointer_ray(&self, cursor: glam::Vec2) -> Option<(glam::Vec3, glam::Vec3)> {
+        let size = self.window.inner_size();
+        if size.width == 0 || size.height == 0 {
+            return None;
+        }
+        let ndc = glam::Vec2::new(
+            cursor.x / size.width as f32 * 2.0 - 1.0,
+            1.0 - cursor.y / size.height as f32 * 2.0,
+        );
+        let inverse = (self.camera.projection() * self.camera.view()).inverse();
+        let near = inverse.project_point3(ndc.extend(0.0));
+        let far = inverse.project_point3(ndc.extend(1.0));
+        Some((near, (far - near).normalize_or_zero()))
+    }
+
+    // Najwyższy widoczny obrazek pod kursorem i punkt na nim (w jednostkach `size`)
+    pub fn pick_image(&mut self, cursor: glam::Vec2) -> Option<(String, glam::Vec2)> {
+        let (origin, dir) = self.pointer_ray(cursor)?;
+        let mut hits = Vec::new();
+        for (index, name) in self.draw_order.iter().enumerate() {
+            let Some(image) = self.images.get(name) else {
+                continue;
+            };
+            if !self.is_image_visible(image) {
+                continue;
+            }
+            if let Some((_, uv)) = image.intersect(origin, dir) {
+                hits.push((stage_rank(image.stage), index, name.clone(), uv));
+            }
+        }
+        // na wierzchu: późniejszy stage, potem późniejszy w kolejności rysowania
+        hits.sort_by(|a, b| (b.0, b.1).cmp(&(a.0, a.1)));
+
+        for (_, _, name, uv) in hits {
+            let image = &self.images[&name];
+            if image.hit_alpha > 0.0 {
+                let path = image.texture_path.clone();
+                let threshold = image.hit_alpha;
+                let mask = self
+                    .hit_masks
+                    .entry(path.clone())
+                    .or_insert_with(|| AlphaMask::load(&path).map_err(|e| log::warn!("{e}")).ok());
+                if mask
+                    .as_ref()
+                    .is_some_and(|mask| mask.alpha_at(uv) < threshold)
+                {
+                    continue;
+                }
+            }
+            let size = self.images[&name].size;
+            return Some((name, uv * size));
+        }
+        None
+    }
+
+    pub fn pointer_moved(&mut self, window_id: u32, cursor: glam::Vec2) {
+        self.cursor = Some(cursor);
+        let hit = self.pick_image(cursor);
+        let hovered = hit.as_ref().map(|(name, _)| name.clone());
+        if hovered == self.hovered {
+            return;
+        }
+        if let Some(name) = self.hovered.take() {
+            emit(OutboundEvent::ImageHoverLeave {
+                window: window_id,
+                name,
+            });
+        }
+        if let Some((name, point)) = hit {
+            emit(OutboundEvent::ImageHoverEnter {
+                window: window_id,
+                name: name.clone(),
+                x: point.x,
+                y: point.y,
+            });
+            self.hovered = Some(name);
+        }
+    }
+
+    pub fn pointer_left(&mut self, window_id: u32) {
+        self.cursor = None;
+        self.pressed.clear();
+        if let Some(name) = self.hovered.take() {
+            emit(OutboundEvent::ImageHoverLeave {
+                window: window_id,
+                name,
+            });
+        }
+    }
+
+    // Kliknięcie = wciśnięcie i puszczenie przycisku nad tym samym obrazkiem
+    pub fn pointer_button(&mut self, window_id: u32, button: PointerButton, pressed: bool) {
+        let Some(cursor) = self.cursor else {
+            return;
+        };
+        let hit = self.pick_image(cursor);
+        if pressed {
+            match hit {
+                Some((name, _)) => {
+                    self.pressed.insert(button, name);
+                }
+                None => {
+                    self.pressed.remove(&button);
+                }
+            }
+            return;
+        }
+        let Some(pressed_on) = self.pressed.remove(&button) else {
+            return;
+        };
+        if let Some((name, point)) = hit.filter(|(name, _)| *name == pressed_on) {
+            emit(OutboundEvent::ImageClicked {
+                window: window_id,
+                name,
+                x: point.x,
+                y: point.y,
+                button,
+            });
+        }
+    }
+}
+
+impl State {
+    // `threshold` 0 = trafia cały prostokąt, np. 0.5 = tylko piksele z alpha >= 0.5
+    pub fn set_image_hit_alpha(
+        &mut self,
+        window_id: u32,
+        name: &str,
+        threshold: f32,
+    ) -> anyhow::Result<()> {
+        let ws = self
+            .windows
+            .get_mut(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+        let image = ws
+            .images
+            .get_mut(name)
+            .ok_or_else(|| anyhow::anyhow!("No image with name '{}'", name))?;
+        image.hit_alpha = threshold.clamp(0.0, 1.0);
+        Ok(())
+    }
+}
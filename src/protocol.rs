@@ -0,0 +1,199 @@
+//! Newline-delimited JSON protocol: commands on stdin, replies on stdout.
+use serde::{Deserialize, Serialize};
+
+use crate::{Command, CustomUserEvent};
+
+// Jedna linia wejścia, np. {"id": 1, "command": {"DeleteImage": {"window": 0, "name": "logo"}}}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Request {
+    // `id` wraca w odpowiedzi, żeby klient mógł je dopasować
+    #[serde(default)]
+    pub id: Option<u64>,
+    pub command: Command,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reply {
+    pub id: Option<u64>,
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Reply {
+    pub fn from_result(result: &anyhow::Result<Option<serde_json::Value>>) -> Self {
+        match result {
+            Ok(value) => Self {
+                id: None,
+                ok: true,
+                result: value.clone(),
+                error: None,
+            },
+            Err(e) => Self::error(None, e.to_string()),
+        }
+    }
+
+    pub fn error(id: Option<u64>, error: String) -> Self {
+        Self {
+            id,
+            ok: false,
+            result: None,
+            error: Some(error),
+        }
+    }
+}
+
+// Wywoływany w pętli zdarzeń po wykonaniu komendy
+#[cfg(not(target_arch = "wasm32"))]
+pub type Responder = Box<dyn FnOnce(Reply) + Send>;
+#[cfg(target_arch = "wasm32")]
+pub type Responder = Box<dyn FnOnce(Reply)>;
+
+// Ile wierszy może czekać na wolnego klienta, zanim kolejne zaczną przepadać
+#[cfg(not(target_arch = "wasm32"))]
+const CLIENT_QUEUE_LEN: usize = 1024;
+
+// Wiersze do klienta pisze osobny wątek, więc wolny klient nie blokuje pętli zdarzeń
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone)]
+pub struct LineWriter {
+    name: std::sync::Arc<str>,
+    sender: std::sync::mpsc::SyncSender<String>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl LineWriter {
+    pub fn spawn<W: std::io::Write + Send + 'static>(name: &str, mut out: W) -> Self {
+        let (sender, receiver) = std::sync::mpsc::sync_channel::<String>(CLIENT_QUEUE_LEN);
+        let name: std::sync::Arc<str> = name.into();
+        {
+            let name = name.clone();
+            std::thread::spawn(move || {
+                for line in receiver {
+                    if let Err(e) = writeln!(out, "{}", line).and_then(|_| out.flush()) {
+                        log::warn!("Failed to write to {}: {}", name, e);
+                        break;
+                    }
+                }
+            });
+        }
+        Self { name, sender }
+    }
+
+    // Przy pełnej kolejce wiersz przepada – lepiej zgubić zdarzenie niż zatrzymać renderowanie
+    pub fn send(&self, value: &impl Serialize) {
+        let line = match serde_json::to_string(value) {
+            Ok(line) => line,
+            Err(e) => {
+                log::error!("Failed to serialize message for {}: {}", self.name, e);
+                return;
+            }
+        };
+        if let Err(std::sync::mpsc::TrySendError::Full(_)) = self.sender.try_send(line) {
+            log::warn!("Dropping message to {}: queue is full", self.name);
+        }
+    }
+}
+
+// stdout jest zarezerwowany dla protokołu – logi idą na stderr
+#[cfg(not(target_arch = "wasm32"))]
+pub fn serve_stdin(proxy: winit::event_loop::EventLoopProxy<CustomUserEvent>) {
+    use std::io::BufRead;
+
+    let writer = LineWriter::spawn("stdout", std::io::stdout());
+    {
+        let writer = writer.clone();
+        crate::subscribe(Box::new(move |event| {
+            writer.send(&serde_json::json!({ "event": event }))
+        }));
+    }
+
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => {
+                    log::error!("Failed to read stdin: {}", e);
+                    break;
+                }
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let request: Request = match serde_json::from_str(&line) {
+                Ok(request) => request,
+                Err(e) => {
+                    writer.send(&Reply::error(None, format!("Invalid request: {}", e)));
+                    continue;
+                }
+            };
+            let id = request.id;
+            let responder: Responder = {
+                let writer = writer.clone();
+                Box::new(move |mut reply| {
+                    reply.id = id;
+                    writer.send(&reply);
+                })
+            };
+            // pętla zdarzeń już się zakończyła
+            if proxy
+                .send_event(CustomUserEvent::Command(request.command, Some(responder)))
+                .is_err()
+            {
+                break;
+            }
+        }
+        log::info!("stdin closed");
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn ok_reply_carries_result_without_error() {
+        let mut reply = Reply::from_result(&Ok(Some(json!({"width": 800}))));
+        reply.id = Some(7);
+        assert_eq!(
+            serde_json::to_value(&reply).unwrap(),
+            json!({"id": 7, "ok": true, "result": {"width": 800}})
+        );
+    }
+
+    #[test]
+    fn ok_reply_without_result_skips_the_field() {
+        let reply = Reply::from_result(&Ok(None));
+        assert_eq!(
+            serde_json::to_value(&reply).unwrap(),
+            json!({"id": null, "ok": true})
+        );
+    }
+
+    #[test]
+    fn error_reply_carries_message() {
+        let reply = Reply::from_result(&Err(anyhow::anyhow!("No window with id 3")));
+        assert_eq!(
+            serde_json::to_value(&reply).unwrap(),
+            json!({"id": null, "ok": false, "error": "No window with id 3"})
+        );
+        assert_eq!(
+            serde_json::to_value(Reply::error(Some(2), "Invalid request".to_string())).unwrap(),
+            json!({"id": 2, "ok": false, "error": "Invalid request"})
+        );
+    }
+
+    #[test]
+    fn request_id_is_optional() {
+        let request: Request =
+            serde_json::from_str(r#"{"command": {"DeleteWindow": {"id": 1}}}"#).unwrap();
+        assert_eq!(request.id, None);
+        assert_eq!(request.command, Command::DeleteWindow { id: 1 });
+        let request: Request =
+            serde_json::from_str(r#"{"id": 4, "command": {"DeleteWindow": {"id": 1}}}"#).unwrap();
+        assert_eq!(request.id, Some(4));
+    }
+}
@@ -0,0 +1,206 @@
+//! Recording handled commands to a JSON-lines file and replaying them frame by frame.
+use std::io::Write;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Command, CustomUserEvent};
+
+// Jedna linia pliku. `frame` to liczba klatek wyrenderowanych przed tym wpisem
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RecordEntry {
+    Frame {
+        frame: u64,
+        time: f64,
+    },
+    Command {
+        frame: u64,
+        time: f64,
+        command: Command,
+    },
+}
+
+pub struct Recorder {
+    path: String,
+    out: std::io::BufWriter<std::fs::File>,
+}
+
+impl Recorder {
+    pub fn create(path: &str) -> anyhow::Result<Self> {
+        let file = std::fs::File::create(path)
+            .map_err(|e| anyhow::anyhow!("Failed to create recording '{}': {}", path, e))?;
+        log::info!("Recording commands to '{}'", path);
+        Ok(Self {
+            path: path.to_string(),
+            out: std::io::BufWriter::new(file),
+        })
+    }
+
+    fn write(&mut self, entry: &RecordEntry) {
+        // każda linia od razu trafia na dysk, żeby nagranie przetrwało crash
+        let result = serde_json::to_writer(&mut self.out, entry)
+            .map_err(anyhow::Error::from)
+            .and_then(|_| writeln!(self.out).map_err(anyhow::Error::from))
+            .and_then(|_| self.out.flush().map_err(anyhow::Error::from));
+        if let Err(e) = result {
+            log::error!("Failed to write recording '{}': {}", self.path, e);
+        }
+    }
+
+    pub fn record_event(&mut self, frame: u64, time: f64, event: &CustomUserEvent) {
+        if let Some(command) = Command::from_event(event) {
+            self.write(&RecordEntry::Command {
+                frame,
+                time,
+                command,
+            });
+        }
+    }
+
+    pub fn record_frame(&mut self, frame: u64, time: f64) {
+        self.write(&RecordEntry::Frame { frame, time });
+    }
+}
+
+pub struct Replay {
+    // (klatka, czas obsłużenia, komenda)
+    commands: std::collections::VecDeque<(u64, f64, Command)>,
+    frame_times: std::collections::HashMap<u64, f64>,
+    finished: bool,
+}
+
+impl Replay {
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read recording '{}': {}", path, e))?;
+        Self::parse(&text, path)
+    }
+
+    // `path` służy tylko do komunikatów błędów
+    pub fn parse(text: &str, path: &str) -> anyhow::Result<Self> {
+        let mut commands = std::collections::VecDeque::new();
+        let mut frame_times = std::collections::HashMap::new();
+        for (i, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry: RecordEntry = serde_json::from_str(line).map_err(|e| {
+                anyhow::anyhow!(
+                    "Invalid entry in recording '{}' line {}: {}",
+                    path,
+                    i + 1,
+                    e
+                )
+            })?;
+            match entry {
+                RecordEntry::Frame { frame, time } => {
+                    frame_times.insert(frame, time);
+                }
+                RecordEntry::Command {
+                    frame,
+                    time,
+                    command,
+                } => commands.push_back((frame, time, command)),
+            }
+        }
+        log::info!(
+            "Replaying {} commands over {} frames from '{}'",
+            commands.len(),
+            frame_times.len(),
+            path
+        );
+        Ok(Self {
+            commands,
+            frame_times,
+            finished: false,
+        })
+    }
+
+    // Komendy obsłużone w nagraniu przed klatką `frame`, z czasem ich obsłużenia –
+    // zegar trzeba ustawić na ten czas, zanim komenda (np. przejście opacity) go odczyta
+    pub fn due_events(&mut self, frame: u64) -> Vec<(f64, CustomUserEvent)> {
+        let mut events = Vec::new();
+        while self
+            .commands
+            .front()
+            .is_some_and(|(command_frame, _, _)| *command_frame <= frame)
+        {
+            let (_, time, command) = self.commands.pop_front().unwrap();
+            events.push((time, command.into_event()));
+        }
+        if self.commands.is_empty() && !self.finished {
+            self.finished = true;
+            log::info!("Replay finished at frame {}", frame);
+        }
+        events
+    }
+
+    pub fn frame_time(&self, frame: u64) -> Option<f64> {
+        self.frame_times.get(&frame).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECORDING: &str = r#"
+{"Command":{"frame":0,"time":0.0,"command":{"CreateWindow":{"id":0,"width":800,"height":600,"title":"t","background_color":[0,0,0,1]}}}}
+{"Frame":{"frame":0,"time":0.016}}
+{"Frame":{"frame":1,"time":0.033}}
+{"Command":{"frame":2,"time":0.04,"command":{"DeleteImage":{"window":0,"name":"a"}}}}
+{"Command":{"frame":2,"time":0.045,"command":{"DeleteImage":{"window":0,"name":"b"}}}}
+{"Frame":{"frame":2,"time":0.05}}
+{"Command":{"frame":5,"time":0.1,"command":{"DeleteWindow":{"id":0}}}}
+"#;
+
+    fn commands(events: Vec<(f64, CustomUserEvent)>) -> Vec<(f64, Command)> {
+        events
+            .into_iter()
+            .map(|(time, event)| (time, Command::from_event(&event).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn due_events_releases_each_command_once_in_order() {
+        let mut replay = Replay::parse(RECORDING, "test.jsonl").unwrap();
+        let first = commands(replay.due_events(0));
+        assert_eq!(first.len(), 1);
+        assert!(matches!(first[0].1, Command::CreateWindow { id: 0, .. }));
+        assert!(replay.due_events(0).is_empty());
+        assert!(replay.due_events(1).is_empty());
+
+        let image = |name: &str| Command::DeleteImage {
+            window: 0,
+            name: name.to_string(),
+        };
+        assert_eq!(
+            commands(replay.due_events(2)),
+            [(0.04, image("a")), (0.045, image("b"))]
+        );
+        // klatki pominięte – wszystko, co zaległe, wychodzi naraz
+        assert_eq!(
+            commands(replay.due_events(9)),
+            [(0.1, Command::DeleteWindow { id: 0 })]
+        );
+        assert!(replay.due_events(10).is_empty());
+    }
+
+    #[test]
+    fn frame_time_comes_from_frame_entries() {
+        let replay = Replay::parse(RECORDING, "test.jsonl").unwrap();
+        assert_eq!(replay.frame_time(0), Some(0.016));
+        assert_eq!(replay.frame_time(2), Some(0.05));
+        assert_eq!(replay.frame_time(3), None);
+    }
+
+    #[test]
+    fn invalid_line_is_reported_with_its_number() {
+        let error = Replay::parse(
+            "\n{\"Frame\":{\"frame\":0,\"time\":0.0}}\nnot json\n",
+            "bad.jsonl",
+        )
+        .err()
+        .unwrap();
+        assert!(error.to_string().contains("line 3"), "{}", error);
+    }
+}
@@ -0,0 +1,117 @@
+//! WebSocket remote control for the wasm build, speaking the same JSON protocol as native.
+use wasm_bindgen::{prelude::Closure, JsCast};
+
+use crate::{CustomUserEvent, Reply, Request, Responder};
+
+// Co ile ms próbujemy połączyć się ponownie po zerwaniu połączenia
+const RECONNECT_DELAY_MS: u32 = 2000;
+
+// Adres podaje się w adresie strony: `index.html?remote=ws://localhost:7878`
+pub fn remote_from_url() -> Option<String> {
+    let search = web_sys::window()?.location().search().ok()?;
+    web_sys::UrlSearchParams::new_with_str(&search)
+        .ok()?
+        .get("remote")
+}
+
+pub fn connect(url: &str) -> anyhow::Result<()> {
+    let socket = web_sys::WebSocket::new(url)
+        .map_err(|e| anyhow::anyhow!("Failed to connect to '{}': {:?}", url, e))?;
+
+    // zdarzenia idą do serwera przez cały czas życia połączenia
+    let subscription = {
+        let socket = socket.clone();
+        crate::subscribe(Box::new(move |event| {
+            if socket.ready_state() == web_sys::WebSocket::OPEN {
+                send_line(&socket, &serde_json::json!({ "event": event }));
+            }
+        }))
+    };
+
+    let onopen = {
+        let url = url.to_string();
+        Closure::<dyn FnMut()>::new(move || log::info!("Connected to '{}'", url))
+    };
+    socket.set_onopen(Some(onopen.as_ref().unchecked_ref()));
+    onopen.forget();
+
+    let onmessage = {
+        let socket = socket.clone();
+        Closure::<dyn FnMut(web_sys::MessageEvent)>::new(move |event: web_sys::MessageEvent| {
+            let Some(text) = event.data().as_string() else {
+                log::warn!("Ignoring binary WebSocket message");
+                return;
+            };
+            handle_message(&socket, &text);
+        })
+    };
+    socket.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+    onmessage.forget();
+
+    // połączenie zerwane (albo nieudane) – próbujemy dalej, backend mógł się zrestartować
+    let onclose = {
+        let url = url.to_string();
+        Closure::<dyn FnMut()>::new(move || {
+            crate::unsubscribe(subscription);
+            log::warn!("Connection to '{}' closed, reconnecting", url);
+            let url = url.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                crate::sleep_for(RECONNECT_DELAY_MS).await;
+                if let Err(e) = connect(&url) {
+                    log::error!("{e}");
+                }
+            });
+        })
+    };
+    socket.set_onclose(Some(onclose.as_ref().unchecked_ref()));
+    onclose.forget();
+    Ok(())
+}
+
+fn handle_message(socket: &web_sys::WebSocket, text: &str) {
+    let request: Request = match serde_json::from_str(text) {
+        Ok(request) => request,
+        Err(e) => {
+            send_line(
+                socket,
+                &Reply::error(None, format!("Invalid request: {}", e)),
+            );
+            return;
+        }
+    };
+    let Some(proxy) = crate::get_global_proxy() else {
+        send_line(
+            socket,
+            &Reply::error(request.id, "Event loop proxy is not set".to_string()),
+        );
+        return;
+    };
+
+    let id = request.id;
+    let responder: Responder = {
+        let socket = socket.clone();
+        Box::new(move |mut reply| {
+            reply.id = id;
+            send_line(&socket, &reply);
+        })
+    };
+    if proxy
+        .send_event(CustomUserEvent::Command(request.command, Some(responder)))
+        .is_err()
+    {
+        log::error!("Event loop is closed");
+    }
+}
+
+fn send_line(socket: &web_sys::WebSocket, value: &impl serde::Serialize) {
+    let result = serde_json::to_string(value)
+        .map_err(anyhow::Error::from)
+        .and_then(|text| {
+            socket
+                .send_with_str(&text)
+                .map_err(|e| anyhow::anyhow!("{:?}", e))
+        });
+    if let Err(e) = result {
+        log::warn!("Failed to send to '{}': {}", socket.url(), e);
+    }
+}
@@ -0,0 +1,272 @@
+//! Parent/child hierarchy between named images and models.
+use renderling::stage::Vertex;
+
+use crate::{ImageObject, State, WindowState};
+
+impl ImageObject {
+    // Lokalny układ obrazka: początek w rogu (vertex 0), obrót wokół środka
+    pub fn local_matrix(&self) -> glam::Mat4 {
+        let pivot = (self.size / 2.0).extend(0.0);
+        glam::Mat4::from_translation(self.position + pivot)
+            * glam::Mat4::from_rotation_z(self.rotation)
+            * glam::Mat4::from_translation(-pivot)
+    }
+
+    fn rebuild_vertices(&mut self, parent: glam::Mat4) {
+        let world = parent * self.local_matrix();
+        let (w, h) = (self.size.x, self.size.y);
+        let corners = [
+            ([0.0, 0.0], [0.0, 1.0]),
+            ([w, 0.0], [1.0, 1.0]),
+            ([0.0, h], [0.0, 0.0]),
+            ([w, 0.0], [1.0, 1.0]),
+            ([w, h], [1.0, 0.0]),
+            ([0.0, h], [0.0, 0.0]),
+        ];
+        self.vertices_cpu = corners
+            .iter()
+            .map(|([x, y], uv)| {
+                let position = world.transform_point3(glam::Vec3::new(*x, *y, 0.0));
+                Vertex::default().with_position(position).with_uv0(*uv)
+            })
+            .collect();
+    }
+}
+
+// dziecko -> rodzic
+type Parents = std::collections::HashMap<String, String>;
+
+fn children_of(parents: &Parents, name: &str) -> Vec<String> {
+    let mut children: Vec<String> = parents
+        .iter()
+        .filter(|(_, parent)| parent.as_str() == name)
+        .map(|(child, _)| child.clone())
+        .collect();
+    children.sort();
+    children
+}
+
+// Wszyscy potomkowie, od najbliższych do najdalszych
+fn descendants(parents: &Parents, name: &str) -> Vec<String> {
+    let mut result = children_of(parents, name);
+    let mut i = 0;
+    while i < result.len() {
+        let children = children_of(parents, &result[i]);
+        result.extend(children);
+        i += 1;
+    }
+    result
+}
+
+// Kolejność usuwania: od liści, żeby nie przepinać dzieci, które zaraz i tak znikną
+fn delete_order(parents: &Parents, name: &str, recursive: bool) -> Vec<String> {
+    let mut names = vec![name.to_string()];
+    if recursive {
+        names.extend(descendants(parents, name));
+    }
+    names.reverse();
+    names
+}
+
+// Czy `child` jest samym `parent` albo jednym z jego przodków
+fn creates_cycle(parents: &Parents, child: &str, parent: &str) -> bool {
+    let mut ancestor = Some(parent);
+    while let Some(name) = ancestor {
+        if name == child {
+            return true;
+        }
+        ancestor = parents.get(name).map(String::as_str);
+    }
+    false
+}
+
+impl WindowState {
+    fn has_object(&self, name: &str) -> bool {
+        self.images.contains_key(name) || self.models.contains_key(name)
+    }
+
+    fn children_of(&self, name: &str) -> Vec<String> {
+        children_of(&self.parents, name)
+    }
+
+    fn parent_transform(&self, name: &str) -> glam::Mat4 {
+        self.parents
+            .get(name)
+            .map(|parent| self.world_transform(parent))
+            .unwrap_or(glam::Mat4::IDENTITY)
+    }
+
+    pub fn world_transform(&self, name: &str) -> glam::Mat4 {
+        let local = if let Some(image) = self.images.get(name) {
+            image.local_matrix()
+        } else if let Some(model) = self.models.get(name) {
+            model.local_matrix()
+        } else {
+            glam::Mat4::IDENTITY
+        };
+        self.parent_transform(name) * local
+    }
+
+    // Przelicza transformację świata obiektu i całego jego poddrzewa
+    pub fn update_object_transform(&mut self, name: &str) {
+        let parent = self.parent_transform(name);
+        if let Some(image) = self.images.get_mut(name) {
+            image.rebuild_vertices(parent);
+            self.upload_image(name);
+        } else if let Some(model) = self.models.get(name) {
+            model.update_transform(parent);
+        }
+        for child in self.children_of(name) {
+            self.update_object_transform(&child);
+        }
+    }
+
+    // Usuwa obiekt z hierarchii – jego dzieci przechodzą do jego rodzica
+    pub fn unlink_object(&mut self, name: &str) {
+        let parent = self.parents.remove(name);
+        for child in self.children_of(name) {
+            match &parent {
+                Some(parent) => {
+                    self.parents.insert(child.clone(), parent.clone());
+                }
+                None => {
+                    self.parents.remove(&child);
+                }
+            }
+            self.update_object_transform(&child);
+        }
+    }
+}
+
+impl State {
+    // Transformacja dziecka zostaje lokalna – od teraz jest liczona względem rodzica
+    pub fn set_parent(&mut self, window_id: u32, child: &str, parent: &str) -> anyhow::Result<()> {
+        let ws = self
+            .windows
+            .get_mut(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+        for name in [child, parent] {
+            if !ws.has_object(name) {
+                return Err(anyhow::anyhow!("No object with name '{}'", name));
+            }
+        }
+        if creates_cycle(&ws.parents, child, parent) {
+            return Err(anyhow::anyhow!(
+                "Object '{}' can't be a child of its own descendant '{}'",
+                child,
+                parent
+            ));
+        }
+
+        ws.parents.insert(child.to_string(), parent.to_string());
+        ws.update_object_transform(child);
+        ws.sort_images();
+        Ok(())
+    }
+
+    pub fn detach_from_parent(&mut self, window_id: u32, child: &str) -> anyhow::Result<()> {
+        let ws = self
+            .windows
+            .get_mut(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+        ws.parents
+            .remove(child)
+            .ok_or_else(|| anyhow::anyhow!("Object '{}' has no parent", child))?;
+        ws.update_object_transform(child);
+        ws.sort_images();
+        Ok(())
+    }
+
+    // Bez `recursive` dzieci usuwanego obiektu przechodzą do jego rodzica
+    pub fn delete_object(
+        &mut self,
+        window_id: u32,
+        name: &str,
+        recursive: bool,
+    ) -> anyhow::Result<()> {
+        let ws = self
+            .windows
+            .get(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+        if !ws.has_object(name) {
+            return Err(anyhow::anyhow!("No object with name '{}'", name));
+        }
+        for name in &delete_order(&ws.parents, name, recursive) {
+            let is_image = self.windows[&window_id].images.contains_key(name);
+            if is_image {
+                self.delete_image(window_id, name)?;
+            } else {
+                self.delete_model(window_id, name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parents(links: &[(&str, &str)]) -> Parents {
+        links
+            .iter()
+            .map(|(child, parent)| (child.to_string(), parent.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn rejects_parenting_to_self_or_descendant() {
+        let parents = parents(&[("arm", "body"), ("hand", "arm")]);
+        assert!(creates_cycle(&parents, "body", "body"));
+        assert!(creates_cycle(&parents, "body", "hand"));
+        assert!(creates_cycle(&parents, "arm", "hand"));
+        assert!(!creates_cycle(&parents, "hand", "body"));
+        assert!(!creates_cycle(&parents, "leg", "body"));
+    }
+
+    #[test]
+    fn descendants_are_listed_nearest_first() {
+        let parents = parents(&[
+            ("arm", "body"),
+            ("leg", "body"),
+            ("hand", "arm"),
+            ("finger", "hand"),
+            ("tree", "ground"),
+        ]);
+        assert_eq!(
+            descendants(&parents, "body"),
+            ["arm", "leg", "hand", "finger"]
+        );
+        assert_eq!(descendants(&parents, "finger"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn recursive_delete_removes_leaves_first() {
+        let parents = parents(&[
+            ("arm", "body"),
+            ("leg", "body"),
+            ("hand", "arm"),
+            ("foot", "leg"),
+            ("tree", "ground"),
+        ]);
+        let order = delete_order(&parents, "body", true);
+        assert_eq!(order.len(), 5);
+        let position = |name: &str| order.iter().position(|n| n == name).unwrap();
+        for (child, parent) in [
+            ("arm", "body"),
+            ("leg", "body"),
+            ("hand", "arm"),
+            ("foot", "leg"),
+        ] {
+            assert!(
+                position(child) < position(parent),
+                "{} before {}",
+                child,
+                parent
+            );
+        }
+        assert_eq!(order.last().unwrap(), "body");
+
+        assert_eq!(delete_order(&parents, "body", false), ["body"]);
+    }
+}
@@ -0,0 +1,760 @@
+//! Saving and loading a window's scene as RON or JSON.
+use std::collections::BTreeMap;
+
+use renderling::camera::Camera;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    blend::PREMULTIPLIED_SUFFIX, decoded_image, BlendMode, ClosePolicy, LightDescriptor,
+    MaterialDescriptor, RedrawPolicy, RenderLayer, ShadowSettings, State, DEFAULT_LAYER,
+};
+
+// Mapy są posortowane, żeby zapisane sceny dawały stabilne diffy w repozytorium
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SceneDescription {
+    pub background_color: [f32; 4],
+    pub camera: Option<CameraDescription>,
+    pub close_policy: ClosePolicy,
+    pub redraw_policy: RedrawPolicy,
+    pub lighting: bool,
+    pub environment: Option<EnvironmentDescription>,
+    pub textures: Vec<String>,
+    pub layers: BTreeMap<String, RenderLayer>,
+    pub materials: BTreeMap<String, MaterialDescriptor>,
+    pub lights: BTreeMap<String, LightDescription>,
+    pub images: BTreeMap<String, ImageDescription>,
+    pub models: BTreeMap<String, ModelDescription>,
+}
+
+impl Default for SceneDescription {
+    fn default() -> Self {
+        Self {
+            background_color: [0.1, 0.2, 0.3, 1.0],
+            camera: None,
+            close_policy: ClosePolicy::default(),
+            redraw_policy: RedrawPolicy::default(),
+            lighting: false,
+            environment: None,
+            textures: Vec::new(),
+            layers: BTreeMap::new(),
+            materials: BTreeMap::new(),
+            lights: BTreeMap::new(),
+            images: BTreeMap::new(),
+            models: BTreeMap::new(),
+        }
+    }
+}
+
+// Macierze kolumnowo, tak jak `glam::Mat4::to_cols_array`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraDescription {
+    pub projection: [f32; 16],
+    pub view: [f32; 16],
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnvironmentDescription {
+    pub hdr_path: String,
+    pub exposure: f32,
+    pub rotation: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LightDescription {
+    pub light: LightDescriptor,
+    #[serde(default)]
+    pub shadows: Option<ShadowSettings>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImageDescription {
+    pub texture: String,
+    // transformacja lokalna, względem rodzica
+    pub position: [f32; 3],
+    // w jednostkach świata; None = rozmiar tekstury
+    pub size: Option<[f32; 2]>,
+    pub rotation: f32,
+    pub layer: String,
+    pub z_order: i32,
+    pub blend: BlendMode,
+    pub opacity: f32,
+    pub tint: [f32; 4],
+    pub visible: bool,
+    pub hit_alpha: f32,
+    pub cast_shadows: bool,
+    pub material: Option<String>,
+    pub parent: Option<String>,
+}
+
+impl Default for ImageDescription {
+    fn default() -> Self {
+        Self {
+            texture: String::new(),
+            position: [0.0, 0.0, 0.0],
+            size: None,
+            rotation: 0.0,
+            layer: DEFAULT_LAYER.to_string(),
+            z_order: 0,
+            blend: BlendMode::Alpha,
+            opacity: 1.0,
+            tint: [1.0, 1.0, 1.0, 1.0],
+            visible: true,
+            hit_alpha: 0.0,
+            cast_shadows: false,
+            material: None,
+            parent: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelDescription {
+    pub path: String,
+    pub position: [f32; 3],
+    // kwaternion xyzw
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+    pub cast_shadows: bool,
+    pub material: Option<String>,
+    pub animations: BTreeMap<String, AnimationDescription>,
+    pub parent: Option<String>,
+}
+
+impl Default for ModelDescription {
+    fn default() -> Self {
+        Self {
+            path: String::new(),
+            position: [0.0, 0.0, 0.0],
+            rotation: [0.0, 0.0, 0.0, 1.0],
+            scale: [1.0, 1.0, 1.0],
+            cast_shadows: true,
+            material: None,
+            animations: BTreeMap::new(),
+            parent: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnimationDescription {
+    pub time: f32,
+    pub speed: f32,
+    pub looping: bool,
+    pub playing: bool,
+    pub weight: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileFormat {
+    Ron,
+    Json,
+}
+
+impl FileFormat {
+    pub fn from_path(path: &str) -> anyhow::Result<Self> {
+        match std::path::Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+        {
+            Some("ron") => Ok(FileFormat::Ron),
+            Some("json") => Ok(FileFormat::Json),
+            _ => Err(anyhow::anyhow!(
+                "Unknown file format of '{}', expected .ron or .json",
+                path
+            )),
+        }
+    }
+}
+
+impl SceneDescription {
+    pub fn to_text(&self, format: FileFormat) -> anyhow::Result<String> {
+        Ok(match format {
+            FileFormat::Ron => ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?,
+            FileFormat::Json => serde_json::to_string_pretty(self)?,
+        })
+    }
+
+    pub fn from_text(format: FileFormat, text: &str) -> anyhow::Result<Self> {
+        Ok(match format {
+            FileFormat::Ron => ron::from_str(text)?,
+            FileFormat::Json => serde_json::from_str(text)?,
+        })
+    }
+
+    // Odwołania wewnątrz sceny, które `apply_scene` musi móc rozwiązać
+    fn validate(&self) -> anyhow::Result<()> {
+        let has_object =
+            |name: &str| self.images.contains_key(name) || self.models.contains_key(name);
+        for name in self.images.keys() {
+            if self.models.contains_key(name) {
+                return Err(anyhow::anyhow!(
+                    "Scene has both an image and a model named '{}'",
+                    name
+                ));
+            }
+        }
+        let references = self
+            .images
+            .iter()
+            .map(|(name, image)| (name, image.material.as_ref(), image.parent.as_ref()))
+            .chain(
+                self.models
+                    .iter()
+                    .map(|(name, model)| (name, model.material.as_ref(), model.parent.as_ref())),
+            );
+        for (name, material, parent) in references {
+            if let Some(material) = material {
+                if !self.materials.contains_key(material) {
+                    return Err(anyhow::anyhow!(
+                        "Object '{}' uses unknown material '{}'",
+                        name,
+                        material
+                    ));
+                }
+            }
+            if let Some(parent) = parent {
+                if !has_object(parent) {
+                    return Err(anyhow::anyhow!(
+                        "Object '{}' has unknown parent '{}'",
+                        name,
+                        parent
+                    ));
+                }
+            }
+        }
+        for (name, image) in &self.images {
+            if image.layer != DEFAULT_LAYER && !self.layers.contains_key(&image.layer) {
+                return Err(anyhow::anyhow!(
+                    "Image '{}' is on unknown layer '{}'",
+                    name,
+                    image.layer
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl State {
+    pub fn describe_scene(&self, window_id: u32) -> anyhow::Result<SceneDescription> {
+        let ws = self
+            .windows
+            .get(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+
+        // warianty tekstur dla trybów mieszania powstają same przy wczytywaniu obrazków
+        let mut textures: Vec<String> = ws
+            .tex
+            .keys()
+            .filter(|key| !key.ends_with(PREMULTIPLIED_SUFFIX))
+            .cloned()
+            .collect();
+        textures.sort();
+
+        let images = ws
+            .images
+            .iter()
+            .map(|(name, image)| {
+                let description = ImageDescription {
+                    texture: image.texture_path.clone(),
+                    position: image.position.to_array(),
+                    size: Some(image.size.to_array()),
+                    rotation: image.rotation,
+                    layer: image.layer.clone(),
+                    z_order: image.z_order,
+                    blend: image.blend,
+                    // trwające przejście zapisujemy od razu jako stan końcowy
+                    opacity: image.fade.map(|fade| fade.to).unwrap_or(image.opacity),
+                    tint: image.tint,
+                    visible: image.visible,
+                    hit_alpha: image.hit_alpha,
+                    cast_shadows: image.cast_shadows,
+                    material: image.material_name.clone(),
+                    parent: ws.parents.get(name).cloned(),
+                };
+                (name.clone(), description)
+            })
+            .collect();
+
+        let models = ws
+            .models
+            .iter()
+            .map(|(name, model)| {
+                let animations = model
+                    .animations
+                    .iter()
+                    .map(|(anim_name, playback)| {
+                        let description = AnimationDescription {
+                            time: playback.time,
+                            speed: playback.speed,
+                            looping: playback.looping,
+                            playing: playback.playing,
+                            weight: playback.weight,
+                        };
+                        (anim_name.clone(), description)
+                    })
+                    .collect();
+                let description = ModelDescription {
+                    path: model.path.clone(),
+                    position: model.position.to_array(),
+                    rotation: model.rotation.to_array(),
+                    scale: model.scale.to_array(),
+                    cast_shadows: model.cast_shadows,
+                    material: model.material_name.clone(),
+                    animations,
+                    parent: ws.parents.get(name).cloned(),
+                };
+                (name.clone(), description)
+            })
+            .collect();
+
+        Ok(SceneDescription {
+            background_color: ws.background_color,
+            camera: Some(CameraDescription {
+                projection: ws.camera.projection().to_cols_array(),
+                view: ws.camera.view().to_cols_array(),
+            }),
+            close_policy: ws.close_policy,
+            redraw_policy: ws.redraw_policy,
+            lighting: ws.lighting,
+            environment: ws.environment.as_ref().map(|env| EnvironmentDescription {
+                hdr_path: env.hdr_path.clone(),
+                exposure: env.exposure,
+                rotation: env.rotation,
+            }),
+            textures,
+            layers: ws
+                .layers
+                .iter()
+                .map(|(name, layer)| (name.clone(), layer.clone()))
+                .collect(),
+            materials: ws
+                .materials
+                .iter()
+                .map(|(name, named)| (name.clone(), named.descriptor.clone()))
+                .collect(),
+            lights: ws
+                .lights
+                .iter()
+                .map(|(name, light)| {
+                    let description = LightDescription {
+                        light: light.descriptor,
+                        shadows: light.shadow.as_ref().map(|(settings, _)| *settings),
+                    };
+                    (name.clone(), description)
+                })
+                .collect(),
+            images,
+            models,
+        })
+    }
+
+    pub fn save_scene(&self, window_id: u32, path: &str) -> anyhow::Result<()> {
+        let text = self
+            .describe_scene(window_id)?
+            .to_text(FileFormat::from_path(path)?)?;
+        std::fs::write(path, text)
+            .map_err(|e| anyhow::anyhow!("Failed to write scene '{}': {}", path, e))?;
+        log::info!("Scene of window {} saved to '{}'", window_id, path);
+        Ok(())
+    }
+
+    pub fn load_scene(&mut self, window_id: u32, path: &str) -> anyhow::Result<()> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read scene '{}': {}", path, e))?;
+        let scene = SceneDescription::from_text(FileFormat::from_path(path)?, &text)?;
+        self.replace_scene(window_id, scene)?;
+        log::info!("Scene '{}' loaded into window {}", path, window_id);
+        Ok(())
+    }
+
+    // Najpierw przygotowuje wszystko, co może się nie udać (odwołania, pliki, dekodowanie
+    // tekstur), a dopiero potem czyści okno. Jeśli mimo to budowa się wysypie, wraca
+    // poprzednia scena.
+    pub fn replace_scene(&mut self, window_id: u32, scene: SceneDescription) -> anyhow::Result<()> {
+        self.prepare_scene(&scene)?;
+        let previous = self.describe_scene(window_id)?;
+        self.clear_scene(window_id)?;
+        let Err(e) = self.apply_scene(window_id, scene) else {
+            return Ok(());
+        };
+        log::error!(
+            "Failed to build scene in window {}, restoring the previous one",
+            window_id
+        );
+        let restored = self
+            .clear_scene(window_id)
+            .and_then(|_| self.apply_scene(window_id, previous));
+        if let Err(restore_error) = restored {
+            log::error!("Failed to restore the previous scene: {}", restore_error);
+        }
+        Err(e)
+    }
+
+    fn prepare_scene(&mut self, scene: &SceneDescription) -> anyhow::Result<()> {
+        scene.validate()?;
+        let textures = scene
+            .textures
+            .iter()
+            .chain(scene.images.values().map(|image| &image.texture))
+            .chain(scene.materials.values().flat_map(|material| {
+                [
+                    &material.albedo_texture,
+                    &material.normal_map,
+                    &material.occlusion_map,
+                ]
+                .into_iter()
+                .flatten()
+            }));
+        for path in textures {
+            decoded_image(&mut self.decoded_images, path)?;
+        }
+        let files = scene
+            .models
+            .values()
+            .map(|model| &model.path)
+            .chain(scene.environment.iter().map(|env| &env.hdr_path));
+        for path in files {
+            if !std::path::Path::new(path).is_file() {
+                return Err(anyhow::anyhow!("No such file '{}'", path));
+            }
+        }
+        Ok(())
+    }
+
+    // Usuwa obiekty, światła, materiały i warstwy okna. Środowisko zostaje –
+    // scena z własnym `environment` po prostu je podmieni.
+    pub fn clear_scene(&mut self, window_id: u32) -> anyhow::Result<()> {
+        let ws = self
+            .windows
+            .get(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+        let images: Vec<String> = ws.images.keys().cloned().collect();
+        let models: Vec<String> = ws.models.keys().cloned().collect();
+        let lights: Vec<String> = ws.lights.keys().cloned().collect();
+        let materials: Vec<String> = ws.materials.keys().cloned().collect();
+        let layers: Vec<String> = ws
+            .layers
+            .keys()
+            .filter(|name| name.as_str() != DEFAULT_LAYER)
+            .cloned()
+            .collect();
+
+        for name in images {
+            self.delete_image(window_id, &name)?;
+        }
+        for name in models {
+            self.delete_model(window_id, &name)?;
+        }
+        for name in lights {
+            self.delete_light(window_id, &name)?;
+        }
+        for name in materials {
+            self.delete_material(window_id, &name)?;
+        }
+        for name in layers {
+            self.delete_layer(window_id, &name)?;
+        }
+        self.set_layer(window_id, DEFAULT_LAYER.to_string(), 0, false)?;
+        self.set_layer_visible(window_id, DEFAULT_LAYER, true)?;
+        Ok(())
+    }
+
+    pub fn apply_scene(&mut self, window_id: u32, scene: SceneDescription) -> anyhow::Result<()> {
+        let ws = self
+            .windows
+            .get_mut(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))?;
+        ws.background_color = scene.background_color;
+        ws.stage.set_background_color(scene.background_color);
+        if let Some(camera) = &scene.camera {
+            ws.set_camera(Camera::new(
+                glam::Mat4::from_cols_array(&camera.projection),
+                glam::Mat4::from_cols_array(&camera.view),
+            ));
+        }
+        self.set_close_policy(window_id, scene.close_policy)?;
+        self.set_redraw_policy(window_id, scene.redraw_policy)?;
+        self.set_lighting(window_id, scene.lighting)?;
+
+        if let Some(env) = &scene.environment {
+            self.set_environment(window_id, &env.hdr_path)?;
+            self.set_environment_exposure(window_id, env.exposure)?;
+            if env.rotation != 0.0 {
+                self.set_environment_rotation(window_id, env.rotation)?;
+            }
+        }
+        for (name, layer) in &scene.layers {
+            self.set_layer(window_id, name.clone(), layer.order, layer.ignore_depth)?;
+            self.set_layer_visible(window_id, name, layer.visible)?;
+        }
+        for (name, descriptor) in scene.materials {
+            self.create_material(window_id, name, descriptor)?;
+        }
+        for path in &scene.textures {
+            self.load_texture(window_id, path)?;
+        }
+        for (name, light) in scene.lights {
+            self.add_light(window_id, name.clone(), light.light)?;
+            if let Some(settings) = light.shadows {
+                self.enable_shadows(window_id, &name, settings)?;
+            }
+        }
+
+        let mut parents = Vec::new();
+        for (name, image) in scene.images {
+            let [x, y, z] = image.position;
+            self.add_image(window_id, name.clone(), image.texture.clone(), x, y, z)?;
+            let ws = self.windows.get_mut(&window_id).unwrap();
+            let object = ws.images.get_mut(&name).unwrap();
+            if let Some(size) = image.size {
+                object.size = size.into();
+            }
+            object.rotation = image.rotation;
+            object.tint = image.tint;
+            object.opacity = image.opacity;
+            object.visible = image.visible;
+            object.hit_alpha = image.hit_alpha;
+            object.cast_shadows = image.cast_shadows;
+            object.apply_color();
+            ws.update_object_transform(&name);
+
+            for step in image_steps(&image) {
+                match step {
+                    ImageStep::Layer(layer) => self.set_image_layer(window_id, &name, layer)?,
+                    ImageStep::ZOrder(z_order) => {
+                        self.set_image_z_order(window_id, &name, z_order)?
+                    }
+                    ImageStep::Blend(blend) => {
+                        self.set_image_blend_mode(window_id, &name, blend)?
+                    }
+                    ImageStep::Material(material) => {
+                        self.assign_material(window_id, &name, material)?
+                    }
+                }
+            }
+            if let Some(parent) = image.parent {
+                parents.push((name, parent));
+            }
+        }
+
+        for (name, model) in scene.models {
+            let [x, y, z] = model.position;
+            self.load_model(window_id, name.clone(), model.path.clone(), x, y, z)?;
+            let ws = self.windows.get_mut(&window_id).unwrap();
+            let object = ws.models.get_mut(&name).unwrap();
+            object.rotation = glam::Quat::from_array(model.rotation);
+            object.scale = model.scale.into();
+            object.cast_shadows = model.cast_shadows;
+            ws.update_object_transform(&name);
+            if let Some(material) = &model.material {
+                self.assign_material(window_id, &name, material)?;
+            }
+
+            for (anim_name, anim) in &model.animations {
+                self.seek_animation(window_id, &name, anim_name, anim.time)?;
+                let ws = self.windows.get_mut(&window_id).unwrap();
+                if let Some(playback) = ws
+                    .models
+                    .get_mut(&name)
+                    .and_then(|object| object.animations.get_mut(anim_name))
+                {
+                    playback.speed = anim.speed;
+                    playback.looping = anim.looping;
+                    playback.playing = anim.playing;
+                }
+                self.set_animation_weight(window_id, &name, anim_name, anim.weight)?;
+            }
+            if let Some(parent) = model.parent {
+                parents.push((name, parent));
+            }
+        }
+
+        for (child, parent) in parents {
+            self.set_parent(window_id, &child, &parent)?;
+        }
+        Ok(())
+    }
+}
+
+// Kolejność odtwarzania obrazka – materiał na końcu, gdy obrazek jest już na stage'u swojej
+// warstwy i w swoim trybie mieszania, więc dostaje tekstury z właściwego atlasu
+#[derive(Debug, PartialEq)]
+enum ImageStep<'a> {
+    Layer(&'a str),
+    ZOrder(i32),
+    Blend(BlendMode),
+    Material(&'a str),
+}
+
+fn image_steps(image: &ImageDescription) -> Vec<ImageStep<'_>> {
+    let mut steps = vec![
+        ImageStep::Layer(&image.layer),
+        ImageStep::ZOrder(image.z_order),
+        ImageStep::Blend(image.blend),
+    ];
+    if let Some(material) = &image.material {
+        steps.push(ImageStep::Material(material));
+    }
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_scene() -> SceneDescription {
+        let mut scene = SceneDescription {
+            camera: Some(CameraDescription {
+                projection: glam::Mat4::perspective_rh(1.0, 16.0 / 9.0, 0.1, 100.0).to_cols_array(),
+                view: glam::Mat4::look_at_rh(
+                    glam::Vec3::new(0.0, 1.0, 5.0),
+                    glam::Vec3::ZERO,
+                    glam::Vec3::Y,
+                )
+                .to_cols_array(),
+            }),
+            close_policy: ClosePolicy::Notify,
+            redraw_policy: RedrawPolicy::MaxFps(30),
+            lighting: true,
+            environment: Some(EnvironmentDescription {
+                hdr_path: "assets/sky.hdr".to_string(),
+                exposure: 1.5,
+                rotation: 0.25,
+            }),
+            textures: vec!["assets/logo.png".to_string()],
+            ..Default::default()
+        };
+        scene.layers.insert(
+            "ui".to_string(),
+            RenderLayer {
+                order: 10,
+                ignore_depth: true,
+                visible: false,
+            },
+        );
+        scene.materials.insert(
+            "gold".to_string(),
+            MaterialDescriptor {
+                albedo_color: [1.0, 0.8, 0.2, 1.0],
+                metallic: 1.0,
+                roughness: 0.3,
+                ..Default::default()
+            },
+        );
+        scene.lights.insert(
+            "sun".to_string(),
+            LightDescription {
+                light: LightDescriptor::Directional {
+                    direction: [0.0, -1.0, -0.5],
+                    color: [1.0, 1.0, 1.0, 1.0],
+                    intensity: 2.0,
+                },
+                shadows: Some(ShadowSettings::default()),
+            },
+        );
+        scene.images.insert(
+            "logo".to_string(),
+            ImageDescription {
+                texture: "assets/logo.png".to_string(),
+                position: [1.0, 2.0, 0.5],
+                size: Some([0.64, 0.32]),
+                layer: "ui".to_string(),
+                z_order: 3,
+                blend: BlendMode::Additive,
+                opacity: 0.5,
+                material: Some("gold".to_string()),
+                parent: Some("robot".to_string()),
+                ..Default::default()
+            },
+        );
+        scene.models.insert(
+            "robot".to_string(),
+            ModelDescription {
+                path: "assets/robot.glb".to_string(),
+                scale: [2.0, 2.0, 2.0],
+                material: Some("gold".to_string()),
+                animations: BTreeMap::from([(
+                    "walk".to_string(),
+                    AnimationDescription {
+                        time: 0.5,
+                        speed: -1.0,
+                        looping: true,
+                        playing: true,
+                        weight: 0.75,
+                    },
+                )]),
+                ..Default::default()
+            },
+        );
+        scene
+    }
+
+    #[test]
+    fn scene_round_trips_through_ron_and_json() {
+        let scene = sample_scene();
+        for format in [FileFormat::Ron, FileFormat::Json] {
+            let text = scene.to_text(format).unwrap();
+            let loaded = SceneDescription::from_text(format, &text).unwrap();
+            assert_eq!(loaded, scene, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn image_on_overlay_layer_keeps_material_after_reload() {
+        let scene = sample_scene();
+        let text = scene.to_text(FileFormat::Ron).unwrap();
+        let loaded = SceneDescription::from_text(FileFormat::Ron, &text).unwrap();
+        let image = &loaded.images["logo"];
+        assert_eq!(image, &scene.images["logo"]);
+        assert_eq!(
+            image_steps(image),
+            vec![
+                ImageStep::Layer("ui"),
+                ImageStep::ZOrder(3),
+                ImageStep::Blend(BlendMode::Additive),
+                ImageStep::Material("gold"),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_fields_get_defaults() {
+        let scene = SceneDescription::from_text(FileFormat::Json, "{}").unwrap();
+        assert_eq!(scene, SceneDescription::default());
+        let scene = SceneDescription::from_text(FileFormat::Ron, "()").unwrap();
+        assert_eq!(scene, SceneDescription::default());
+    }
+
+    #[test]
+    fn validation_rejects_dangling_references() {
+        let mut scene = sample_scene();
+        assert!(scene.validate().is_ok());
+        scene.images.get_mut("logo").unwrap().parent = Some("ghost".to_string());
+        assert!(scene.validate().is_err());
+
+        let mut scene = sample_scene();
+        scene.models.get_mut("robot").unwrap().material = Some("silver".to_string());
+        assert!(scene.validate().is_err());
+
+        let mut scene = sample_scene();
+        scene.layers.clear();
+        assert!(scene.validate().is_err());
+    }
+
+    #[test]
+    fn file_format_follows_extension() {
+        assert_eq!(
+            FileFormat::from_path("a/scene.ron").unwrap(),
+            FileFormat::Ron
+        );
+        assert_eq!(
+            FileFormat::from_path("scene.json").unwrap(),
+            FileFormat::Json
+        );
+        assert!(FileFormat::from_path("scene.yaml").is_err());
+    }
+}
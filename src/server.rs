@@ -0,0 +1,388 @@
+//! Render server: the JSON-lines protocol over a localhost TCP port or a Unix domain socket.
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::ToSocketAddrs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::protocol::LineWriter;
+use crate::{Command, CustomUserEvent, OutboundEvent, Reply, Request, Responder, RunOptions};
+
+// Istniejące okna i ich właściciele pod jedną blokadą, żeby sprawdzenie i zajęcie id były atomowe
+#[derive(Default)]
+struct Windows {
+    live: HashSet<u32>,
+    // okno -> klient, który je utworzył; okna bez wpisu (demo, skrypt) należą do serwera
+    owners: HashMap<u32, u64>,
+}
+
+type Registry = Arc<Mutex<Windows>>;
+
+// `addr` to `127.0.0.1:7878` albo `unix:/tmp/renderling.sock`
+pub fn serve(addr: &str, options: &RunOptions) -> anyhow::Result<()> {
+    let proxy =
+        crate::get_global_proxy().ok_or_else(|| anyhow::anyhow!("Event loop proxy is not set"))?;
+    let registry = Registry::default();
+    // okna otwarte i zamknięte z dowolnego źródła, także spoza serwera
+    {
+        let registry = registry.clone();
+        crate::subscribe(Box::new(move |event| track_window(&registry, event)));
+    }
+    // None = katalog bieżący
+    let scene_dir: Arc<Path> = PathBuf::from(options.scene_dir.as_deref().unwrap_or(".")).into();
+
+    if let Some(path) = addr.strip_prefix("unix:") {
+        #[cfg(unix)]
+        {
+            remove_stale_socket(path)?;
+            let listener = std::os::unix::net::UnixListener::bind(path)
+                .map_err(|e| anyhow::anyhow!("Failed to listen on '{}': {}", addr, e))?;
+            log::info!("Listening on '{}'", addr);
+            std::thread::spawn(move || {
+                for (client, stream) in (1..).zip(listener.incoming()) {
+                    match stream.and_then(|s| Ok((s.try_clone()?, s))) {
+                        Ok((reader, writer)) => spawn_client(
+                            client,
+                            reader,
+                            writer,
+                            registry.clone(),
+                            scene_dir.clone(),
+                            proxy.clone(),
+                        ),
+                        Err(e) => log::error!("Failed to accept client: {}", e),
+                    }
+                }
+            });
+            return Ok(());
+        }
+        #[cfg(not(unix))]
+        return Err(anyhow::anyhow!(
+            "Unix sockets are not supported on this platform: '{}'",
+            path
+        ));
+    }
+
+    check_bind_addr(addr, options.allow_remote)?;
+    let listener = std::net::TcpListener::bind(addr)
+        .map_err(|e| anyhow::anyhow!("Failed to listen on '{}': {}", addr, e))?;
+    log::info!("Listening on '{}'", addr);
+    std::thread::spawn(move || {
+        for (client, stream) in (1..).zip(listener.incoming()) {
+            match stream.and_then(|s| Ok((s.try_clone()?, s))) {
+                Ok((reader, writer)) => spawn_client(
+                    client,
+                    reader,
+                    writer,
+                    registry.clone(),
+                    scene_dir.clone(),
+                    proxy.clone(),
+                ),
+                Err(e) => log::error!("Failed to accept client: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+// Plik po poprzednim uruchomieniu blokowałby bind – usuwamy go tylko, jeśli to martwe gniazdo
+#[cfg(unix)]
+fn remove_stale_socket(path: &str) -> anyhow::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return Ok(());
+    };
+    if !metadata.file_type().is_socket() {
+        return Err(anyhow::anyhow!("'{}' exists and is not a socket", path));
+    }
+    if std::os::unix::net::UnixStream::connect(path).is_ok() {
+        return Err(anyhow::anyhow!("Socket '{}' is already in use", path));
+    }
+    std::fs::remove_file(path)
+        .map_err(|e| anyhow::anyhow!("Failed to remove stale socket '{}': {}", path, e))
+}
+
+fn track_window(registry: &Registry, event: &OutboundEvent) {
+    match *event {
+        OutboundEvent::WindowOpened { window } => {
+            registry.lock().unwrap().live.insert(window);
+        }
+        // okno zamknięte także przez użytkownika przestaje należeć do klienta
+        OutboundEvent::WindowClosed { window } => {
+            let mut windows = registry.lock().unwrap();
+            windows.live.remove(&window);
+            windows.owners.remove(&window);
+        }
+        _ => {}
+    }
+}
+
+// Protokół nie ma uwierzytelniania, więc bez `allow_remote` słuchamy tylko na loopbacku
+fn check_bind_addr(addr: &str, allow_remote: bool) -> anyhow::Result<()> {
+    if allow_remote {
+        return Ok(());
+    }
+    let addrs: Vec<_> = addr
+        .to_socket_addrs()
+        .map_err(|e| anyhow::anyhow!("Invalid listen address '{}': {}", addr, e))?
+        .collect();
+    if addrs.iter().any(|a| !a.ip().is_loopback()) {
+        return Err(anyhow::anyhow!(
+            "Refusing to listen on non-loopback address '{}' without --allow-remote",
+            addr
+        ));
+    }
+    Ok(())
+}
+
+fn spawn_client<R, W>(
+    client: u64,
+    reader: R,
+    writer: W,
+    registry: Registry,
+    scene_dir: Arc<Path>,
+    proxy: winit::event_loop::EventLoopProxy<CustomUserEvent>,
+) where
+    R: Read + Send + 'static,
+    W: Write + Send + 'static,
+{
+    std::thread::spawn(move || {
+        log::info!("Client {} connected", client);
+        let writer = LineWriter::spawn(&format!("client {}", client), writer);
+        let subscription = {
+            let writer = writer.clone();
+            let registry = registry.clone();
+            crate::subscribe(Box::new(move |event| {
+                // zdarzenia okien innych klientów do niego nie trafiają
+                let owner = event
+                    .window()
+                    .and_then(|window| registry.lock().unwrap().owners.get(&window).copied());
+                if owner.map_or(true, |owner| owner == client) {
+                    writer.send(&serde_json::json!({ "event": event }));
+                }
+            }))
+        };
+        for line in BufReader::new(reader).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let mut request: Request = match serde_json::from_str(&line) {
+                Ok(request) => request,
+                Err(e) => {
+                    writer.send(&Reply::error(None, format!("Invalid request: {}", e)));
+                    continue;
+                }
+            };
+            if let Err(e) = claim_window(client, &request.command, &registry)
+                .and_then(|_| confine_scene_path(&mut request.command, &scene_dir))
+            {
+                writer.send(&Reply::error(request.id, e.to_string()));
+                continue;
+            }
+
+            let id = request.id;
+            let window = request.command.window();
+            let created = matches!(request.command, Command::CreateWindow { .. });
+            let deleted = matches!(request.command, Command::DeleteWindow { .. });
+            let responder: Responder = {
+                let writer = writer.clone();
+                let registry = registry.clone();
+                Box::new(move |mut reply| {
+                    // nieudane utworzenie zwalnia okno, udane usunięcie też
+                    if (created && !reply.ok) || (deleted && reply.ok) {
+                        registry.lock().unwrap().owners.remove(&window);
+                    }
+                    reply.id = id;
+                    writer.send(&reply);
+                })
+            };
+            if proxy
+                .send_event(CustomUserEvent::Command(request.command, Some(responder)))
+                .is_err()
+            {
+                break;
+            }
+        }
+
+        crate::unsubscribe(subscription);
+
+        // okna rozłączonego klienta znikają razem z nim
+        let windows: Vec<u32> = {
+            let mut registry = registry.lock().unwrap();
+            let owners = &mut registry.owners;
+            let windows = owners
+                .iter()
+                .filter(|(_, owner)| **owner == client)
+                .map(|(window, _)| *window)
+                .collect();
+            owners.retain(|_, owner| *owner != client);
+            windows
+        };
+        for id in windows {
+            let _ = proxy.send_event(CustomUserEvent::Command(Command::DeleteWindow { id }, None));
+        }
+        log::info!("Client {} disconnected", client);
+    });
+}
+
+// Okno utworzone przez klienta należy tylko do niego; okna bez właściciela należą do serwera.
+// CreateWindow zajmuje id od razu, więc komendy wysłane za nim bez czekania trafiają do tego okna
+fn claim_window(client: u64, command: &Command, registry: &Registry) -> anyhow::Result<()> {
+    let window = command.window();
+    let creates = matches!(command, Command::CreateWindow { .. });
+    let mut windows = registry.lock().unwrap();
+    match windows.owners.get(&window) {
+        Some(owner) if *owner != client => Err(anyhow::anyhow!(
+            "Window {} belongs to another client",
+            window
+        )),
+        Some(_) if creates => Err(anyhow::anyhow!("Window with id {} already exists", window)),
+        Some(_) => Ok(()),
+        None if creates => {
+            if windows.live.contains(&window) {
+                return Err(anyhow::anyhow!("Window with id {} already exists", window));
+            }
+            windows.owners.insert(window, client);
+            Ok(())
+        }
+        None => Err(anyhow::anyhow!(
+            "Window {} was not created by this client",
+            window
+        )),
+    }
+}
+
+// Klient zapisuje i wczytuje sceny tylko wewnątrz `scene_dir`, podając ścieżki względne
+fn confine_scene_path(command: &mut Command, scene_dir: &Path) -> anyhow::Result<()> {
+    let (Command::SaveScene { path, .. } | Command::LoadScene { path, .. }) = command else {
+        return Ok(());
+    };
+    let relative = Path::new(path.as_str());
+    let inside = relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    if !inside {
+        return Err(anyhow::anyhow!(
+            "Scene path '{}' must be relative to the scene directory",
+            path
+        ));
+    }
+    *path = scene_dir.join(relative).to_string_lossy().into_owned();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delete_image(window: u32) -> Command {
+        Command::DeleteImage {
+            window,
+            name: "logo".to_string(),
+        }
+    }
+
+    fn create_window(id: u32) -> Command {
+        Command::CreateWindow {
+            id,
+            width: 800,
+            height: 600,
+            title: "test".to_string(),
+            background_color: [0.0, 0.0, 0.0, 1.0],
+            visible: true,
+        }
+    }
+
+    #[test]
+    fn windows_belong_to_their_creator() {
+        let registry = Registry::default();
+        claim_window(1, &create_window(5), &registry).unwrap();
+        claim_window(1, &delete_image(5), &registry).unwrap();
+        assert!(claim_window(2, &delete_image(5), &registry).is_err());
+        assert!(claim_window(2, &create_window(5), &registry).is_err());
+        assert!(claim_window(1, &create_window(5), &registry).is_err());
+    }
+
+    #[test]
+    fn windows_without_owner_belong_to_the_server() {
+        let registry = Registry::default();
+        assert!(claim_window(1, &delete_image(0), &registry).is_err());
+        assert!(claim_window(1, &Command::DeleteWindow { id: 0 }, &registry).is_err());
+        assert!(registry.lock().unwrap().owners.is_empty());
+    }
+
+    #[test]
+    fn pipelined_create_does_not_take_over_server_window() {
+        let registry = Registry::default();
+        track_window(&registry, &OutboundEvent::WindowOpened { window: 0 });
+        // oba wiersze przychodzą, zanim pętla zdarzeń odpowie na pierwszy
+        assert!(claim_window(1, &create_window(0), &registry).is_err());
+        assert!(claim_window(1, &Command::DeleteWindow { id: 0 }, &registry).is_err());
+        assert!(registry.lock().unwrap().owners.is_empty());
+    }
+
+    #[test]
+    fn closed_window_is_released() {
+        let registry = Registry::default();
+        claim_window(1, &create_window(3), &registry).unwrap();
+        track_window(&registry, &OutboundEvent::WindowOpened { window: 3 });
+        track_window(&registry, &OutboundEvent::WindowClosed { window: 3 });
+        claim_window(2, &create_window(3), &registry).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn only_stale_sockets_are_removed() {
+        let dir = std::env::temp_dir().join(format!("renderling-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let file = dir.join("not-a-socket");
+        std::fs::write(&file, "keep").unwrap();
+        assert!(remove_stale_socket(file.to_str().unwrap()).is_err());
+        assert!(file.exists());
+
+        let socket = dir.join("socket");
+        let path = socket.to_str().unwrap();
+        let listener = std::os::unix::net::UnixListener::bind(path).unwrap();
+        assert!(remove_stale_socket(path).is_err());
+        drop(listener);
+        remove_stale_socket(path).unwrap();
+        assert!(!socket.exists());
+        remove_stale_socket(path).unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn scene_paths_stay_inside_scene_dir() {
+        let dir = Path::new("scenes");
+        let mut command = Command::LoadScene {
+            window: 0,
+            path: "./level/intro.ron".to_string(),
+        };
+        confine_scene_path(&mut command, dir).unwrap();
+        let Command::LoadScene { path, .. } = &command else {
+            unreachable!()
+        };
+        assert_eq!(Path::new(path), dir.join("./level/intro.ron"));
+
+        for path in ["../secret.ron", "level/../../secret.ron", "/etc/passwd"] {
+            let mut command = Command::SaveScene {
+                window: 0,
+                path: path.to_string(),
+            };
+            assert!(confine_scene_path(&mut command, dir).is_err(), "{}", path);
+        }
+    }
+
+    #[test]
+    fn only_loopback_without_allow_remote() {
+        check_bind_addr("127.0.0.1:7878", false).unwrap();
+        check_bind_addr("[::1]:7878", false).unwrap();
+        assert!(check_bind_addr("0.0.0.0:7878", false).is_err());
+        check_bind_addr("0.0.0.0:7878", true).unwrap();
+    }
+}
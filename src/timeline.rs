@@ -0,0 +1,199 @@
+//! Timeline scripts: timestamped commands played back through the event loop proxy.
+use serde::{Deserialize, Serialize};
+
+use crate::{scene_io::FileFormat, Command, CustomUserEvent};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Timeline {
+    pub commands: Vec<TimedCommand>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimedCommand {
+    // sekundy od startu odtwarzania
+    pub at: f64,
+    pub command: Command,
+}
+
+// Demo uruchamiane bez skryptu, wkompilowane w binarkę i bundle wasm
+const DEMO_SCRIPT: &str = include_str!("../scripts/demo.ron");
+
+impl Timeline {
+    pub fn demo() -> anyhow::Result<Self> {
+        #[allow(unused_mut)]
+        let mut timeline = Self::parse(DEMO_SCRIPT, "scripts/demo.ron")?;
+        // w przeglądarce okno 0 to canvas strony, tworzony razem ze State
+        #[cfg(target_arch = "wasm32")]
+        timeline
+            .commands
+            .retain(|entry| !matches!(entry.command, Command::CreateWindow { id: 0, .. }));
+        Ok(timeline)
+    }
+
+    // `path` służy tylko do rozpoznania formatu po rozszerzeniu
+    pub fn parse(text: &str, path: &str) -> anyhow::Result<Self> {
+        let mut timeline: Timeline = match FileFormat::from_path(path)? {
+            FileFormat::Ron => ron::from_str(text)?,
+            FileFormat::Json => serde_json::from_str(text)?,
+        };
+        // sortowanie stabilne – komendy z tym samym czasem zostają w kolejności z pliku
+        timeline.commands.sort_by(|a, b| a.at.total_cmp(&b.at));
+        Ok(timeline)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read timeline '{}': {}", path, e))?;
+        Self::parse(&text, path)
+    }
+
+    // Na wasm plik pobieramy z serwera, względem strony
+    #[cfg(target_arch = "wasm32")]
+    pub async fn fetch(url: &str) -> anyhow::Result<Self> {
+        use wasm_bindgen::JsCast;
+        use wasm_bindgen_futures::JsFuture;
+
+        let window = web_sys::window().ok_or_else(|| anyhow::anyhow!("No browser window"))?;
+        let response = JsFuture::from(window.fetch_with_str(url))
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to fetch timeline '{}': {:?}", url, e))?;
+        let response: web_sys::Response = response.unchecked_into();
+        if !response.ok() {
+            return Err(anyhow::anyhow!(
+                "Failed to fetch timeline '{}': HTTP {}",
+                url,
+                response.status()
+            ));
+        }
+        let text = response
+            .text()
+            .map_err(|e| anyhow::anyhow!("Failed to read timeline '{}': {:?}", url, e))?;
+        let text = JsFuture::from(text)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to read timeline '{}': {:?}", url, e))?
+            .as_string()
+            .unwrap_or_default();
+        Self::parse(&text, url)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn play_timeline(
+    timeline: Timeline,
+    proxy: winit::event_loop::EventLoopProxy<CustomUserEvent>,
+) {
+    std::thread::spawn(move || {
+        let start = std::time::Instant::now();
+        for entry in timeline.commands {
+            let wait = entry.at - start.elapsed().as_secs_f64();
+            if wait > 0.0 {
+                std::thread::sleep(std::time::Duration::from_secs_f64(wait));
+            }
+            // pętla zdarzeń już się zakończyła
+            if proxy.send_event(entry.command.into_event()).is_err() {
+                break;
+            }
+        }
+        log::info!("Timeline finished");
+    });
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn play_timeline(
+    timeline: Timeline,
+    proxy: winit::event_loop::EventLoopProxy<CustomUserEvent>,
+) {
+    wasm_bindgen_futures::spawn_local(async move {
+        let start = crate::now_seconds();
+        for entry in timeline.commands {
+            let wait = entry.at - (crate::now_seconds() - start);
+            if wait > 0.0 {
+                crate::sleep_for((wait * 1000.0) as u32).await;
+            }
+            if proxy.send_event(entry.command.into_event()).is_err() {
+                break;
+            }
+        }
+        log::info!("Timeline finished");
+    });
+}
+
+// Na wasm skrypt podaje się w adresie strony: `index.html?script=scripts/demo.ron`
+#[cfg(target_arch = "wasm32")]
+pub fn script_from_url() -> Option<String> {
+    let search = web_sys::window()?.location().search().ok()?;
+    web_sys::UrlSearchParams::new_with_str(&search)
+        .ok()?
+        .get("script")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_are_sorted_by_time_keeping_file_order_for_ties() {
+        let text = r#"{"commands": [
+            {"at": 2.0, "command": {"DeleteImage": {"window": 0, "name": "late"}}},
+            {"at": 1.0, "command": {"DeleteImage": {"window": 0, "name": "first"}}},
+            {"at": 1.0, "command": {"DeleteImage": {"window": 0, "name": "second"}}},
+            {"at": 0.0, "command": {"DeleteWindow": {"id": 3}}}
+        ]}"#;
+        let timeline = Timeline::parse(text, "script.json").unwrap();
+        let order: Vec<(f64, Command)> = timeline
+            .commands
+            .into_iter()
+            .map(|entry| (entry.at, entry.command))
+            .collect();
+        let image = |name: &str| Command::DeleteImage {
+            window: 0,
+            name: name.to_string(),
+        };
+        assert_eq!(
+            order,
+            [
+                (0.0, Command::DeleteWindow { id: 3 }),
+                (1.0, image("first")),
+                (1.0, image("second")),
+                (2.0, image("late")),
+            ]
+        );
+    }
+
+    #[test]
+    fn ron_and_json_scripts_parse_the_same() {
+        let ron = r#"(commands: [(at: 0.5, command: SetLighting(window: 1, enabled: true))])"#;
+        let json = r#"{"commands": [{"at": 0.5, "command": {"SetLighting": {"window": 1, "enabled": true}}}]}"#;
+        let from_ron = Timeline::parse(ron, "a.ron").unwrap();
+        let from_json = Timeline::parse(json, "a.json").unwrap();
+        assert_eq!(from_ron.commands.len(), 1);
+        assert_eq!(from_ron.commands[0].command, from_json.commands[0].command);
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        // nieznane rozszerzenie
+        assert!(Timeline::parse("(commands: [])", "script.txt").is_err());
+        // składnia
+        assert!(Timeline::parse("(commands: [", "script.ron").is_err());
+        // nieznana komenda
+        assert!(Timeline::parse(
+            r#"{"commands": [{"at": 0.0, "command": {"Explode": {}}}]}"#,
+            "script.json"
+        )
+        .is_err());
+        // brak czasu
+        assert!(Timeline::parse(
+            r#"{"commands": [{"command": {"DeleteWindow": {"id": 0}}}]}"#,
+            "script.json"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn demo_script_parses() {
+        let timeline = Timeline::demo().unwrap();
+        assert!(!timeline.commands.is_empty());
+    }
+}
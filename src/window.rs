@@ -0,0 +1,217 @@
+//! Per-window behaviour: close and redraw policies, window management (position, size, fullscreen, cursor).
+use serde::{Deserialize, Serialize};
+
+use crate::{State, WindowState};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ClosePolicy {
+    // zamyka całą aplikację
+    ExitApp,
+    // usuwa tylko to okno; aplikacja kończy się razem z ostatnim oknem
+    #[default]
+    CloseWindow,
+    // nic nie robi – klient dostaje WindowCloseRequested i sam decyduje
+    Notify,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum FullscreenMode {
+    #[default]
+    Off,
+    Borderless,
+    // najlepszy tryb wideo monitora, na którym jest okno
+    Exclusive,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RedrawPolicy {
+    // klatka za klatką, jak dotąd
+    #[default]
+    Continuous,
+    // tylko po komendzie zmieniającej scenę, zmianie rozmiaru albo w trakcie animacji
+    OnDemand,
+    // ciągle, ale nie częściej niż N klatek na sekundę
+    MaxFps(u32),
+}
+
+impl WindowState {
+    // Animacje modeli albo przejścia przezroczystości w toku
+    pub fn is_animating(&self) -> bool {
+        self.models.values().any(|model| model.is_animating())
+            || self.images.values().any(|image| image.fade.is_some())
+    }
+
+    // Kiedy (czas z `now_seconds`) okno chce następnej klatki; None = czeka na zmianę sceny
+    pub fn next_redraw(&self) -> Option<f64> {
+        match self.redraw_policy {
+            RedrawPolicy::Continuous => Some(0.0),
+            RedrawPolicy::OnDemand => (self.needs_redraw || self.is_animating()).then_some(0.0),
+            RedrawPolicy::MaxFps(fps) => {
+                let interval = 1.0 / fps.max(1) as f64;
+                Some(self.last_frame.map_or(0.0, |last| last + interval))
+            }
+        }
+    }
+
+    // Przesuwa animacje, przejścia i cienie do chwili `now`; zwraca dt klatki
+    pub fn advance_frame(&mut self, now: f64) -> f32 {
+        let dt = self.last_frame.map(|last| now - last).unwrap_or(0.0) as f32;
+        self.last_frame = Some(now);
+        for model in self.models.values_mut() {
+            model.advance_animations(dt);
+        }
+        self.advance_fades(now);
+        self.update_shadows();
+        dt
+    }
+}
+
+impl State {
+    // Czy choć jedno okno jest na ekranie – ukryte okna nie dostają `RedrawRequested`
+    pub fn any_window_shown(&self, hidden_windows: bool) -> bool {
+        !hidden_windows
+            && self
+                .windows
+                .values()
+                .any(|ws| ws.window.is_visible() != Some(false))
+    }
+
+    fn window_state(&mut self, window_id: u32) -> anyhow::Result<&mut WindowState> {
+        self.windows
+            .get_mut(&window_id)
+            .ok_or_else(|| anyhow::anyhow!("No window with id {}", window_id))
+    }
+
+    // pozycja w pikselach fizycznych, jak `WindowState::position`
+    pub fn set_window_position(&mut self, window_id: u32, x: i32, y: i32) -> anyhow::Result<()> {
+        let ws = self.window_state(window_id)?;
+        ws.window
+            .set_outer_position(winit::dpi::PhysicalPosition::new(x, y));
+        Ok(())
+    }
+
+    // rozmiar w pikselach logicznych, jak w CreateWindow
+    pub fn set_window_size(
+        &mut self,
+        window_id: u32,
+        width: u32,
+        height: u32,
+    ) -> anyhow::Result<()> {
+        let ws = self.window_state(window_id)?;
+        // None = rozmiar zmieni się później i przyjdzie w `Resized`
+        if let Some(size) = ws
+            .window
+            .request_inner_size(winit::dpi::LogicalSize::new(width, height))
+        {
+            ws.resize(size);
+        }
+        Ok(())
+    }
+
+    pub fn set_window_title(&mut self, window_id: u32, title: &str) -> anyhow::Result<()> {
+        self.window_state(window_id)?.window.set_title(title);
+        Ok(())
+    }
+
+    pub fn set_fullscreen(&mut self, window_id: u32, mode: FullscreenMode) -> anyhow::Result<()> {
+        let ws = self.window_state(window_id)?;
+        let fullscreen = match mode {
+            FullscreenMode::Off => None,
+            FullscreenMode::Borderless => Some(winit::window::Fullscreen::Borderless(None)),
+            FullscreenMode::Exclusive => {
+                let video_mode = ws
+                    .window
+                    .current_monitor()
+                    .and_then(|monitor| {
+                        monitor.video_modes().max_by_key(|mode| {
+                            let size = mode.size();
+                            (size.width * size.height, mode.refresh_rate_millihertz())
+                        })
+                    })
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "No video mode for exclusive fullscreen of window {}",
+                            window_id
+                        )
+                    })?;
+                Some(winit::window::Fullscreen::Exclusive(video_mode))
+            }
+        };
+        ws.window.set_fullscreen(fullscreen);
+        Ok(())
+    }
+
+    pub fn set_decorations(&mut self, window_id: u32, decorations: bool) -> anyhow::Result<()> {
+        self.window_state(window_id)?
+            .window
+            .set_decorations(decorations);
+        Ok(())
+    }
+
+    pub fn set_always_on_top(&mut self, window_id: u32, always_on_top: bool) -> anyhow::Result<()> {
+        let level = if always_on_top {
+            winit::window::WindowLevel::AlwaysOnTop
+        } else {
+            winit::window::WindowLevel::Normal
+        };
+        self.window_state(window_id)?.window.set_window_level(level);
+        Ok(())
+    }
+
+    pub fn set_visible(&mut self, window_id: u32, visible: bool) -> anyhow::Result<()> {
+        self.window_state(window_id)?.window.set_visible(visible);
+        Ok(())
+    }
+
+    pub fn set_cursor_visible(&mut self, window_id: u32, visible: bool) -> anyhow::Result<()> {
+        self.window_state(window_id)?
+            .window
+            .set_cursor_visible(visible);
+        Ok(())
+    }
+
+    // nazwy ikon jak w CSS: "default", "pointer", "crosshair", "text", "grab"...
+    pub fn set_cursor_icon(&mut self, window_id: u32, icon: &str) -> anyhow::Result<()> {
+        let icon: winit::window::CursorIcon = icon
+            .parse()
+            .map_err(|_| anyhow::anyhow!("Unknown cursor icon '{}'", icon))?;
+        self.window_state(window_id)?.window.set_cursor(icon);
+        Ok(())
+    }
+
+    pub fn minimize(&mut self, window_id: u32, minimized: bool) -> anyhow::Result<()> {
+        self.window_state(window_id)?
+            .window
+            .set_minimized(minimized);
+        Ok(())
+    }
+
+    pub fn maximize(&mut self, window_id: u32, maximized: bool) -> anyhow::Result<()> {
+        self.window_state(window_id)?
+            .window
+            .set_maximized(maximized);
+        Ok(())
+    }
+
+    pub fn set_redraw_policy(
+        &mut self,
+        window_id: u32,
+        policy: RedrawPolicy,
+    ) -> anyhow::Result<()> {
+        if policy == RedrawPolicy::MaxFps(0) {
+            return Err(anyhow::anyhow!(
+                "Redraw policy MaxFps for window {} needs at least 1 frame per second",
+                window_id
+            ));
+        }
+        let ws = self.window_state(window_id)?;
+        ws.redraw_policy = policy;
+        ws.needs_redraw = true;
+        Ok(())
+    }
+
+    pub fn set_close_policy(&mut self, window_id: u32, policy: ClosePolicy) -> anyhow::Result<()> {
+        self.window_state(window_id)?.close_policy = policy;
+        Ok(())
+    }
+}